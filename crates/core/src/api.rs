@@ -0,0 +1,115 @@
+use reqwest::{Client, Method, RequestBuilder, Url};
+use serde::de::DeserializeOwned;
+
+use crate::configuration::{Configuration, ConfigurationError};
+use crate::errors::{self, ErrorKind};
+use crate::transport::{http_client, retry_after, SendError};
+
+/// What the parts of the API besides item reporting have in common: the
+/// root they are under, the access token and the HTTP client, which share
+/// the endpoint, timeout and proxy with `HttpTransport`.
+#[derive(Debug, Clone)]
+pub(crate) struct Api {
+    client: Client,
+    root: Url,
+    access_token: String,
+}
+
+/// The body the API answers most successful requests with.
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    result: T,
+}
+
+/// The body the API answers with when a request fails.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    err: u8,
+    message: Option<String>,
+}
+
+impl Api {
+    pub(crate) fn new(configuration: &Configuration) -> errors::Result<Self> {
+        configuration.validate()?;
+        let access_token = configuration
+            .access_token
+            .clone()
+            .ok_or(ErrorKind::MissingAccessToken)?;
+        let root =
+            api_root(&configuration.endpoint).map_err(ConfigurationError::InvalidEndpoint)?;
+        Ok(Api {
+            client: http_client(configuration)?,
+            root,
+            access_token,
+        })
+    }
+
+    /// A request for `path`, relative to the API root.
+    pub(crate) fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, SendError> {
+        let url = self
+            .root
+            .join(path)
+            .map_err(|err| SendError::Request(err.to_string()))?;
+        Ok(self
+            .client
+            .request(method, url)
+            .header("X-Rollbar-Access-Token", self.access_token.as_str()))
+    }
+
+    /// Send `request` and decode the body of a successful answer.
+    pub(crate) async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, SendError> {
+        let resp = request.send().await?;
+        let status = resp.status();
+        let retry_after = retry_after(resp.headers());
+        let body = resp.bytes().await?;
+        if !status.is_success() {
+            let response: Option<ErrorResponse> = serde_json::from_slice(&body).ok();
+            return Err(SendError::Api {
+                status: status.as_u16(),
+                err: response.as_ref().map(|r| r.err),
+                message: response.and_then(|r| r.message),
+                retry_after,
+            });
+        }
+        serde_json::from_slice(&body).map_err(|err| SendError::Response(err.to_string()))
+    }
+
+    /// Like `send`, for the endpoints that wrap what they answer with in
+    /// `{"err": 0, "result": ...}`.
+    pub(crate) async fn result<T: DeserializeOwned>(
+        request: RequestBuilder,
+    ) -> Result<T, SendError> {
+        let envelope: Envelope<T> = Api::send(request).await?;
+        Ok(envelope.result)
+    }
+}
+
+/// The root of the API the item `endpoint` is under, e.g.
+/// `https://api.rollbar.com/api/1/`.
+fn api_root(endpoint: &str) -> Result<Url, String> {
+    let mut url = Url::parse(endpoint).map_err(|err| format!("{}: {}", endpoint, err))?;
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.join("../")
+        .map_err(|err| format!("{}: {}", endpoint, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_root() {
+        for endpoint in [
+            "https://api.rollbar.com/api/1/item/",
+            "https://api.rollbar.com/api/1/item",
+        ] {
+            assert_eq!(
+                api_root(endpoint).unwrap().as_str(),
+                "https://api.rollbar.com/api/1/"
+            );
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::Duration;
+
+use crate::configuration::Configuration;
+use crate::constants;
+use crate::errors::*;
+use crate::hooks::{BeforeSend, Decision, Hooks};
+use crate::scope::Scope;
+use crate::telemetry::TelemetryBuffer;
+use crate::transport::{HttpTransport, SendError, SendFuture, Transport, TransportStats};
+use crate::types::*;
+use crate::Uuid;
+
+/// A notifier that fills in the `Data` defaults from its `Configuration`
+/// and hands finished items to a `Transport`.
+pub struct Rollbar {
+    transport: Box<dyn Transport>,
+    notifier: Notifier,
+    telemetry: TelemetryBuffer,
+    hooks: Hooks,
+}
+
+impl Rollbar {
+    pub fn new(configuration: Configuration) -> Result<Self> {
+        let transport = HttpTransport::new(configuration)?;
+        Ok(Rollbar::with_transport(transport))
+    }
+
+    pub fn with_transport<T: Transport>(transport: T) -> Self {
+        let conf = transport.config();
+        let telemetry = conf.telemetry_type_limits.iter().fold(
+            TelemetryBuffer::new(conf.telemetry_capacity),
+            |buffer, (telemetry_type, limit)| buffer.type_limit(telemetry_type, *limit),
+        );
+        let hooks = Hooks::new(conf);
+        Rollbar {
+            transport: Box::new(transport),
+            notifier: constants::NOTIFIER.clone(),
+            telemetry,
+            hooks,
+        }
+    }
+
+    /// Replace the default `rollbar-rust` notifier, e.g. for language bindings.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Run `hook` on every item before it is sent, after the hooks added so
+    /// far and the ones configured in `Configuration`.
+    pub fn with_before_send<H: BeforeSend>(mut self, hook: H) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    pub fn config(&self) -> &Configuration {
+        self.transport.config()
+    }
+
+    pub fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+
+    /// Breadcrumbs recorded here are attached to every item sent afterwards.
+    pub fn telemetry(&self) -> &TelemetryBuffer {
+        &self.telemetry
+    }
+
+    pub fn push_telemetry(&self, telemetry: Telemetry) {
+        self.telemetry.push(telemetry);
+    }
+
+    pub fn log<S: Into<String>>(&self, level: Level, message: S) -> Result<()> {
+        self.message_with_extra(level, message, HashMap::new())
+    }
+
+    pub fn message_with_extra<S: Into<String>>(
+        &self,
+        level: Level,
+        message: S,
+        extra: HashMap<String, Value>,
+    ) -> Result<()> {
+        let message = Message::builder().body(message).extra(extra).build();
+        let body = Body::builder().message(message).build();
+        self.send(Data::builder().body(body).level(level).build())
+    }
+
+    /// Report `error` along with every cause in its `source()` chain.
+    pub fn error<E: StdError + 'static>(&self, error: &E) -> Result<()> {
+        let body = Body::from_error(error);
+        self.send(Data::builder().body(body).level(Level::Error).build())
+    }
+
+    /// Like `error`, for an error whose type has been erased, e.g. a
+    /// `Box<dyn Error>`. Its class is only known for common std errors.
+    pub fn dyn_error(&self, error: &(dyn StdError + 'static)) -> Result<()> {
+        let body = Body::from_dyn_error(error);
+        self.send(Data::builder().body(body).level(Level::Error).build())
+    }
+
+    pub fn debug<S: Into<String>>(&self, message: S) -> Result<()> {
+        self.log(Level::Debug, message)
+    }
+
+    pub fn info<S: Into<String>>(&self, message: S) -> Result<()> {
+        self.log(Level::Info, message)
+    }
+
+    pub fn warning<S: Into<String>>(&self, message: S) -> Result<()> {
+        self.log(Level::Warning, message)
+    }
+
+    pub fn critical<S: Into<String>>(&self, message: S) -> Result<()> {
+        self.log(Level::Critical, message)
+    }
+
+    /// Nothing is sent, and `Ok` returned, when the configuration is not
+    /// `enabled`.
+    pub fn send(&self, data: Data) -> Result<()> {
+        if !self.config().enabled {
+            return Ok(());
+        }
+        let mut item = self.build_item(data)?;
+        match self.hooks.run(&mut item) {
+            Decision::Send => self.transport.send(item),
+            Decision::Drop => debug!("Item dropped by a before_send hook"),
+            Decision::Route(transport) => transport.send(item),
+        }
+        Ok(())
+    }
+
+    /// Like `send`, but the returned future resolves to the id the API
+    /// assigned to the occurrence, or why it was not accepted.
+    pub fn send_async(&self, data: Data) -> Result<SendFuture> {
+        if !self.config().enabled {
+            return Ok(SendFuture::ready(Err(SendError::Ignored)));
+        }
+        let mut item = self.build_item(data)?;
+        Ok(match self.hooks.run(&mut item) {
+            Decision::Send => self.transport.send_async(item),
+            Decision::Drop => SendFuture::ready(Err(SendError::Ignored)),
+            Decision::Route(transport) => transport.send_async(item),
+        })
+    }
+
+    /// Wrap `data` in an `Item`, filling in any fields the caller left unset
+    /// from the active `Scope` and the configuration and attaching the
+    /// buffered telemetry.
+    pub fn build_item(&self, mut data: Data) -> Result<Item> {
+        let conf = self.config();
+        let access_token = conf
+            .access_token
+            .clone()
+            .ok_or(ErrorKind::MissingAccessToken)?;
+
+        Scope::current().apply(&mut data);
+        if !conf.custom.is_empty() {
+            let custom = data.custom.get_or_insert_with(HashMap::new);
+            for (key, value) in &conf.custom {
+                custom.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        if data.person.is_none() {
+            data.person = conf.person.clone();
+        }
+        if data.framework.is_none() {
+            data.framework = conf.framework.clone();
+        }
+        data.notifier.get_or_insert_with(|| self.notifier.clone());
+        data.platform
+            .get_or_insert_with(|| constants::PLATFORM.to_owned());
+        data.uuid.get_or_insert_with(|| Uuid::new().into());
+        if let Some(telemetry @ None) = data.body.telemetry_mut() {
+            if !self.telemetry.is_empty() {
+                *telemetry = Some(self.telemetry.snapshot());
+            }
+        }
+        if data.environment.is_none() {
+            data.environment = conf.environment.clone();
+        }
+
+        let server = data.server.get_or_insert_with(Server::default);
+        server.cpu.get_or_insert_with(|| constants::ARCH.to_owned());
+        if server.host.is_none() {
+            server.host = conf.host.clone();
+        }
+        if server.code_version.is_none() {
+            server.code_version = conf.code_version.clone();
+        }
+        if server.branch.is_none() {
+            server.branch = conf.branch.clone();
+        }
+        if server.root.is_none() {
+            server.root = conf.root.clone();
+        }
+
+        Ok(Item::builder()
+            .access_token(access_token)
+            .data(data)
+            .build())
+    }
+
+    pub fn stats(&self) -> TransportStats {
+        self.transport.stats()
+    }
+
+    /// Wait up to `timeout` for the items reported so far to be sent,
+    /// without shutting the transport down.
+    pub fn flush(&self, timeout: Duration) -> bool {
+        self.transport.flush(timeout)
+    }
+
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        self.transport.shutdown(timeout)
+    }
+}
+
+impl fmt::Debug for Rollbar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rollbar")
+            .field("configuration", self.config())
+            .field("notifier", &self.notifier)
+            .field("hooks", &self.hooks)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubTransport {
+        configuration: Configuration,
+    }
+
+    impl Transport for StubTransport {
+        fn send(&self, _item: Item) {}
+
+        fn config(&self) -> &Configuration {
+            &self.configuration
+        }
+
+        fn shutdown(&self, _timeout: Duration) -> bool {
+            true
+        }
+    }
+
+    fn configuration() -> Configuration {
+        Configuration {
+            access_token: Some("abc123".to_owned()),
+            environment: Some("testing".to_owned()),
+            host: Some("localhost".to_owned()),
+            code_version: Some("v1".to_owned()),
+            framework: Some("actix".to_owned()),
+            branch: Some("main".to_owned()),
+            custom: HashMap::from([("team".to_owned(), Value::from("payments"))]),
+            person: Some(Person::builder().id("42").build()),
+            ..Configuration::default()
+        }
+    }
+
+    #[test]
+    fn test_build_item_fills_defaults() {
+        let rollbar = Rollbar::with_transport(StubTransport {
+            configuration: configuration(),
+        });
+        let item = rollbar.build_item(Data::default()).unwrap();
+
+        assert_eq!(item.access_token, "abc123");
+        assert_eq!(item.data.environment.as_deref(), Some("testing"));
+        assert_eq!(item.data.platform.as_deref(), Some(constants::PLATFORM));
+        assert!(item.data.uuid.is_some());
+        let server = item.data.server.unwrap();
+        assert_eq!(server.host.as_deref(), Some("localhost"));
+        assert_eq!(server.code_version.as_deref(), Some("v1"));
+        assert_eq!(server.branch.as_deref(), Some("main"));
+        assert_eq!(item.data.framework.as_deref(), Some("actix"));
+        assert_eq!(item.data.person.unwrap().id, "42");
+        assert_eq!(item.data.custom.unwrap()["team"], "payments");
+        assert_eq!(
+            item.data.notifier.unwrap().name.as_deref(),
+            Some("rollbar-rust")
+        );
+    }
+
+    #[test]
+    fn test_build_item_keeps_caller_values() {
+        let rollbar = Rollbar::with_transport(StubTransport {
+            configuration: configuration(),
+        });
+        let data = Data::builder()
+            .environment("production")
+            .server(Server::builder().host("web-1").build())
+            .custom(HashMap::from([("team".to_owned(), Value::from("search"))]))
+            .build();
+        let item = rollbar.build_item(data).unwrap();
+
+        assert_eq!(item.data.environment.as_deref(), Some("production"));
+        assert_eq!(item.data.server.unwrap().host.as_deref(), Some("web-1"));
+        assert_eq!(item.data.custom.unwrap()["team"], "search");
+    }
+
+    #[test]
+    fn test_build_item_attaches_telemetry() {
+        let rollbar = Rollbar::with_transport(StubTransport {
+            configuration: configuration(),
+        });
+        rollbar.push_telemetry(Telemetry::log(Level::Info, "starting"));
+        let body = Body::builder()
+            .message(Message::builder().body("hello").build())
+            .build();
+        let item = rollbar
+            .build_item(Data::builder().body(body).build())
+            .unwrap();
+
+        match item.data.body {
+            Body::MessageBody { telemetry, .. } => {
+                let telemetry = telemetry.unwrap();
+                assert_eq!(telemetry.len(), 1);
+                assert_eq!(telemetry[0].body["message"], "starting");
+            }
+            _ => panic!("expected a message body"),
+        }
+        assert_eq!(rollbar.telemetry().len(), 1);
+    }
+
+    #[test]
+    fn test_missing_access_token() {
+        let rollbar = Rollbar::with_transport(StubTransport {
+            configuration: Configuration::default(),
+        });
+
+        match rollbar.info("hello") {
+            Err(Error(ErrorKind::MissingAccessToken, _)) => {}
+            _ => panic!("expected missing access token"),
+        }
+    }
+
+    #[test]
+    fn test_disabled_sends_nothing() {
+        let rollbar = Rollbar::with_transport(StubTransport {
+            configuration: Configuration {
+                enabled: false,
+                ..Configuration::default()
+            },
+        });
+
+        assert!(rollbar.info("hello").is_ok());
+        let sent = rollbar.send_async(Data::default()).unwrap();
+        assert!(matches!(
+            futures::executor::block_on(sent),
+            Err(SendError::Ignored)
+        ));
+    }
+}
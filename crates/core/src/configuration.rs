@@ -1,4 +1,91 @@
-use crate::types::Level;
+use crate::types::{Level, Person};
+use regex::Regex;
+use reqwest::Url;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod loader;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use loader::{ConfigLoader, LoadedConfiguration, Source};
+
+const DEFAULT_SCRUB_FIELDS: &[&str] = &[
+    "passwd",
+    "password",
+    "password_confirmation",
+    "confirm_password",
+    "secret",
+    "secret_key",
+    "api_key",
+    "access_token",
+    "auth_token",
+    "csrf_token",
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "x-rollbar-access-token",
+    "email",
+];
+
+/// What to do with a new item when the transport queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Drop the new item.
+    #[default]
+    DropNewest,
+    /// Drop the item that has been queued the longest.
+    DropOldest,
+    /// Wait up to `overflow_timeout_ms` for room, then drop the new item.
+    Block,
+    /// Drop the oldest item with the lowest level, unless the new item's
+    /// level is no higher.
+    DropLowestLevel,
+}
+
+/// Something an item's fingerprint can be computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FingerprintPart {
+    /// The outermost exception class.
+    Class,
+    /// The message with numbers, UUIDs and hex strings replaced.
+    Message,
+    /// The top `fingerprint_frames` in-app frames, without line numbers.
+    Frames,
+}
+
+/// How to fingerprint and title the items a rule matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GroupingRule {
+    /// Only match items with this outermost exception class. An entry ending
+    /// in `.` matches a whole package.
+    pub exception_class: Option<String>,
+    /// Only match items whose message matches this regex.
+    pub message: Option<String>,
+    pub fingerprint: Vec<FingerprintPart>,
+    /// A title template, e.g. `{class}: {message}`.
+    pub title: Option<String>,
+}
+
+impl Default for GroupingRule {
+    fn default() -> GroupingRule {
+        GroupingRule {
+            exception_class: None,
+            message: None,
+            fingerprint: vec![
+                FingerprintPart::Class,
+                FingerprintPart::Message,
+                FingerprintPart::Frames,
+            ],
+            title: Some("{class}: {message}".to_owned()),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -8,9 +95,69 @@ pub struct Configuration {
     pub environment: Option<String>,
     pub host: Option<String>,
     pub code_version: Option<String>,
+    pub framework: Option<String>,
+    pub branch: Option<String>,
+    /// The path to the application's root directory on the server.
+    pub root: Option<String>,
+    /// The lowest level of `log` records `RollbarLogger` forwards, unless
+    /// its directives say otherwise. Items reported directly are not
+    /// affected; see `minimum_level`.
     pub log_level: Level,
     pub timeout: u64,
     pub proxy: Option<String>,
+    pub telemetry_capacity: usize,
+    pub telemetry_type_limits: HashMap<String, usize>,
+    pub scrub_fields: Vec<String>,
+    pub scrub_whitelist: Vec<String>,
+    pub scrub_patterns: Vec<String>,
+    pub scrub_mask: String,
+    pub max_item_size: usize,
+    pub max_retries: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+    /// How many items to send per access token and minute, beyond which
+    /// items are dropped. Zero, the default, means no limit.
+    pub items_per_minute: u32,
+    pub spool_dir: Option<String>,
+    pub spool_max_size: u64,
+    pub queue_depth: usize,
+    pub overflow_policy: OverflowPolicy,
+    pub overflow_timeout_ms: u64,
+    pub report_dropped_items: bool,
+    /// Items below this level are not sent, whichever way they were reported.
+    /// `log_level` only decides which `log` records become items at all.
+    pub minimum_level: Option<Level>,
+    /// Exception classes not to report. An entry ending in `.` ignores a
+    /// whole package.
+    pub ignored_exception_classes: Vec<String>,
+    /// Regexes for messages not to report.
+    pub ignored_messages: Vec<String>,
+    /// The fraction of items to keep, by level.
+    pub sample_rates: HashMap<Level, f64>,
+    /// The fraction of items to keep, by fingerprint or exception class.
+    /// Takes precedence over `sample_rates`.
+    pub fingerprint_sample_rates: HashMap<String, f64>,
+    /// Rules for filling in fingerprints and titles. The first matching
+    /// rule applies.
+    pub grouping_rules: Vec<GroupingRule>,
+    /// Frames whose class and method, or file, start with one of these are
+    /// in-app. With none, every frame is.
+    pub in_app_prefixes: Vec<String>,
+    /// How many in-app frames go into a fingerprint.
+    pub fingerprint_frames: usize,
+    /// The environments `environment` may be set to. With none, any
+    /// non-empty name is accepted.
+    pub environments: Vec<String>,
+    /// When false, nothing is reported.
+    pub enabled: bool,
+    /// Added to every item's `custom`. Values set on the item or by a
+    /// `Scope` take precedence.
+    pub custom: HashMap<String, Value>,
+    /// The person items are reported for when neither the item nor a
+    /// `Scope` names one.
+    pub person: Option<Person>,
+    /// How much of `request.user_ip` is sent.
+    pub capture_ip: CaptureIp,
 }
 
 impl Default for Configuration {
@@ -21,9 +168,285 @@ impl Default for Configuration {
             environment: None,
             host: None,
             code_version: None,
+            framework: None,
+            branch: None,
+            root: None,
             log_level: Level::Info,
             timeout: 10,
             proxy: None,
+            telemetry_capacity: 50,
+            telemetry_type_limits: HashMap::new(),
+            scrub_fields: DEFAULT_SCRUB_FIELDS.iter().map(|&f| f.to_owned()).collect(),
+            scrub_whitelist: Vec::new(),
+            scrub_patterns: Vec::new(),
+            scrub_mask: "********".to_owned(),
+            max_item_size: 512 * 1024,
+            max_retries: 3,
+            retry_base_delay_ms: 500,
+            retry_max_delay_ms: 30_000,
+            items_per_minute: 0,
+            spool_dir: None,
+            spool_max_size: 10 * 1024 * 1024,
+            queue_depth: 50,
+            overflow_policy: OverflowPolicy::DropNewest,
+            overflow_timeout_ms: 1000,
+            report_dropped_items: false,
+            minimum_level: None,
+            ignored_exception_classes: Vec::new(),
+            ignored_messages: Vec::new(),
+            sample_rates: HashMap::new(),
+            fingerprint_sample_rates: HashMap::new(),
+            grouping_rules: Vec::new(),
+            in_app_prefixes: Vec::new(),
+            fingerprint_frames: 3,
+            environments: Vec::new(),
+            enabled: true,
+            custom: HashMap::new(),
+            person: None,
+            capture_ip: CaptureIp::Full,
+        }
+    }
+}
+
+/// How much of the client's IP address to report. Besides by name, it can be
+/// set with `true` for `Full` and `false` for `None`, like the other SDKs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", try_from = "CaptureIpSetting")]
+pub enum CaptureIp {
+    #[default]
+    Full,
+    /// Only the network part: the last octet of IPv4 addresses and all but
+    /// the first 48 bits of IPv6 addresses are zeroed.
+    Anonymize,
+    None,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CaptureIpSetting {
+    Enabled(bool),
+    Named(String),
+}
+
+impl TryFrom<CaptureIpSetting> for CaptureIp {
+    type Error = String;
+
+    fn try_from(setting: CaptureIpSetting) -> Result<Self, Self::Error> {
+        match setting {
+            CaptureIpSetting::Enabled(true) => Ok(CaptureIp::Full),
+            CaptureIpSetting::Enabled(false) => Ok(CaptureIp::None),
+            CaptureIpSetting::Named(name) => match name.as_str() {
+                "full" => Ok(CaptureIp::Full),
+                "anonymize" => Ok(CaptureIp::Anonymize),
+                "none" => Ok(CaptureIp::None),
+                _ => Err(format!("unknown capture_ip setting {:?}", name)),
+            },
+        }
+    }
+}
+
+/// Why a `Configuration` cannot be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigurationError {
+    MissingAccessToken,
+    InvalidEndpoint(String),
+    InvalidProxy(String),
+    ZeroTimeout,
+    UnknownEnvironment(String),
+    /// A regex in `scrub_patterns`, `ignored_messages` or `grouping_rules`
+    /// does not compile.
+    InvalidPattern(String),
+}
+
+impl fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigurationError::MissingAccessToken => write!(f, "access_token is missing"),
+            ConfigurationError::InvalidEndpoint(err) => write!(f, "invalid endpoint: {}", err),
+            ConfigurationError::InvalidProxy(err) => write!(f, "invalid proxy: {}", err),
+            ConfigurationError::ZeroTimeout => write!(f, "timeout must be greater than zero"),
+            ConfigurationError::UnknownEnvironment(environment) => {
+                write!(f, "unknown environment {:?}", environment)
+            }
+            ConfigurationError::InvalidPattern(err) => write!(f, "invalid pattern: {}", err),
         }
     }
 }
+
+impl StdError for ConfigurationError {}
+
+impl Configuration {
+    /// Check for settings that would keep any item from being sent. A
+    /// disabled configuration does not need an access token.
+    pub fn validate(&self) -> Result<(), ConfigurationError> {
+        match &self.access_token {
+            Some(token) if !token.trim().is_empty() => {}
+            _ if !self.enabled => {}
+            _ => return Err(ConfigurationError::MissingAccessToken),
+        }
+        http_url(&self.endpoint).map_err(ConfigurationError::InvalidEndpoint)?;
+        if let Some(proxy) = &self.proxy {
+            http_url(proxy).map_err(ConfigurationError::InvalidProxy)?;
+        }
+        if self.timeout == 0 {
+            return Err(ConfigurationError::ZeroTimeout);
+        }
+        // A pattern that is skipped would let through what it was meant to
+        // scrub or ignore.
+        let patterns = self
+            .scrub_patterns
+            .iter()
+            .chain(&self.ignored_messages)
+            .chain(
+                self.grouping_rules
+                    .iter()
+                    .filter_map(|rule| rule.message.as_ref()),
+            );
+        for pattern in patterns {
+            Regex::new(pattern)
+                .map_err(|err| ConfigurationError::InvalidPattern(err.to_string()))?;
+        }
+        if let Some(environment) = &self.environment {
+            let known = if self.environments.is_empty() {
+                !environment.trim().is_empty()
+            } else {
+                self.environments.contains(environment)
+            };
+            if !known {
+                return Err(ConfigurationError::UnknownEnvironment(environment.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn http_url(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|err| format!("{}: {}", url, err))?;
+    match parsed.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(parsed),
+        scheme => Err(format!("{}: unsupported scheme {}", url, scheme)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid() -> Configuration {
+        Configuration {
+            access_token: Some("abc123".to_owned()),
+            ..Configuration::default()
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(valid().validate(), Ok(()));
+        assert_eq!(
+            Configuration::default().validate(),
+            Err(ConfigurationError::MissingAccessToken)
+        );
+        assert!(matches!(
+            Configuration {
+                endpoint: "api.rollbar.com/api/1/item/".to_owned(),
+                ..valid()
+            }
+            .validate(),
+            Err(ConfigurationError::InvalidEndpoint(_))
+        ));
+        assert!(matches!(
+            Configuration {
+                proxy: Some("ftp://proxy".to_owned()),
+                ..valid()
+            }
+            .validate(),
+            Err(ConfigurationError::InvalidProxy(_))
+        ));
+        assert_eq!(
+            Configuration {
+                timeout: 0,
+                ..valid()
+            }
+            .validate(),
+            Err(ConfigurationError::ZeroTimeout)
+        );
+    }
+
+    #[test]
+    fn test_validate_patterns() {
+        let invalid = |configuration: Configuration| {
+            matches!(
+                configuration.validate(),
+                Err(ConfigurationError::InvalidPattern(_))
+            )
+        };
+        assert!(invalid(Configuration {
+            scrub_patterns: vec!["card-(".to_owned()],
+            ..valid()
+        }));
+        assert!(invalid(Configuration {
+            ignored_messages: vec!["[".to_owned()],
+            ..valid()
+        }));
+        assert!(invalid(Configuration {
+            grouping_rules: vec![GroupingRule {
+                message: Some("(?P<".to_owned()),
+                ..GroupingRule::default()
+            }],
+            ..valid()
+        }));
+        assert_eq!(
+            Configuration {
+                scrub_patterns: vec![r"\d{16}".to_owned()],
+                ..valid()
+            }
+            .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_environment() {
+        let configuration = Configuration {
+            environment: Some("prod".to_owned()),
+            environments: vec!["production".to_owned(), "staging".to_owned()],
+            ..valid()
+        };
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::UnknownEnvironment("prod".to_owned()))
+        );
+
+        let configuration = Configuration {
+            environment: Some("staging".to_owned()),
+            ..configuration
+        };
+        assert_eq!(configuration.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_capture_ip_settings() {
+        let capture_ip = |json: &str| {
+            serde_json::from_str::<Configuration>(&format!("{{\"captureIp\": {}}}", json))
+                .map(|configuration| configuration.capture_ip)
+                .ok()
+        };
+        assert_eq!(capture_ip("true"), Some(CaptureIp::Full));
+        assert_eq!(capture_ip("false"), Some(CaptureIp::None));
+        assert_eq!(capture_ip("\"anonymize\""), Some(CaptureIp::Anonymize));
+        assert_eq!(capture_ip("\"partial\""), None);
+        assert_eq!(
+            serde_json::to_value(CaptureIp::Anonymize).unwrap(),
+            "anonymize"
+        );
+    }
+
+    #[test]
+    fn test_disabled_needs_no_access_token() {
+        let configuration = Configuration {
+            enabled: false,
+            ..Configuration::default()
+        };
+        assert_eq!(configuration.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,407 @@
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::Configuration;
+use crate::errors::*;
+
+const ENV_PREFIX: &str = "ROLLBAR_";
+const PROFILE_ENV: &str = "ROLLBAR_PROFILE";
+const PROFILES_KEY: &str = "profile";
+/// Older names still honored for a field, overridden by the canonical one.
+const ENV_ALIASES: &[(&str, &str)] = &[("access_token", "ROLLBAR_TOKEN")];
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    /// A `[profile.<name>]` table in the configuration file.
+    Profile(String),
+    /// The environment variable with this name.
+    Env(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Profile(name) => write!(f, "profile {}", name),
+            Source::Env(name) => write!(f, "${}", name),
+        }
+    }
+}
+
+/// A loaded `Configuration` along with the source of each field.
+#[derive(Debug, Clone)]
+pub struct LoadedConfiguration {
+    pub configuration: Configuration,
+    /// Keyed by field name, e.g. `access_token`.
+    pub sources: BTreeMap<String, Source>,
+}
+
+impl LoadedConfiguration {
+    pub fn source(&self, field: &str) -> Option<&Source> {
+        self.sources.get(field)
+    }
+}
+
+/// Builds a `Configuration` from layers, each overriding the one before:
+///
+/// 1. the defaults,
+/// 2. a TOML or JSON file, if one is given and exists,
+/// 3. the file's `[profile.<name>]` table for the selected profile,
+/// 4. a `ROLLBAR_<FIELD>` environment variable for any field, such as
+///    `ROLLBAR_ACCESS_TOKEN` or `ROLLBAR_ITEMS_PER_MINUTE`.
+///
+/// The profile is the one set with `profile`, else `ROLLBAR_PROFILE`, else
+/// the `environment` the file and environment variables configure. Field
+/// names may be written in snake_case or camelCase. Environment variables
+/// hold JSON for non-string fields, or comma separated values for lists.
+///
+/// ```no_run
+/// use rollbar_rust::configuration::ConfigLoader;
+///
+/// let loaded = ConfigLoader::new().file("rollbar.toml").load().unwrap();
+/// for (field, source) in &loaded.sources {
+///     println!("{} from {}", field, source);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    file: Option<PathBuf>,
+    profile: Option<String>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        ConfigLoader::default()
+    }
+
+    /// Read this file, as JSON if it ends in `.json` and TOML otherwise. A
+    /// missing file is skipped.
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    pub fn profile<S: Into<String>>(mut self, profile: S) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn load(&self) -> Result<LoadedConfiguration> {
+        self.load_with(|name| env::var(name).ok())
+    }
+
+    fn load_with<E: Fn(&str) -> Option<String>>(&self, env: E) -> Result<LoadedConfiguration> {
+        let mut fields = match serde_json::to_value(Configuration::default()) {
+            Ok(Value::Object(fields)) => fields,
+            _ => unreachable!("the configuration serializes to an object"),
+        };
+        let mut sources: BTreeMap<String, Source> = fields
+            .keys()
+            .map(|key| (snake_case(key), Source::Default))
+            .collect();
+
+        let mut profiles = Map::new();
+        if let Some(path) = &self.file {
+            if path.exists() {
+                let mut file = read_file(path)?;
+                if let Some(Value::Object(found)) = file.remove(PROFILES_KEY) {
+                    profiles = found;
+                }
+                merge(&mut fields, &mut sources, file, Source::File(path.clone()))?;
+            } else {
+                debug!("configuration file {} not found", path.display());
+            }
+        }
+
+        let env_fields = env_fields(&fields, &env)?;
+        let profile = self
+            .profile
+            .clone()
+            .or_else(|| env(PROFILE_ENV))
+            .or_else(|| {
+                let environment = env_fields.get("environment").map(|(_, value)| value);
+                environment
+                    .or_else(|| fields.get("environment"))
+                    .and_then(Value::as_str)
+                    .map(str::to_owned)
+            });
+        if let Some(profile) = profile {
+            match profiles.remove(&profile) {
+                Some(Value::Object(overrides)) => merge(
+                    &mut fields,
+                    &mut sources,
+                    overrides,
+                    Source::Profile(profile),
+                )?,
+                Some(_) => bail!(ErrorKind::Configuration(format!(
+                    "profile {} is not a table",
+                    profile
+                ))),
+                None => {}
+            }
+        }
+
+        for (key, (name, value)) in env_fields {
+            sources.insert(snake_case(&key), Source::Env(name));
+            fields.insert(key, value);
+        }
+
+        let configuration = serde_json::from_value(Value::Object(fields))
+            .map_err(|err| ErrorKind::Configuration(err.to_string()))?;
+        Ok(LoadedConfiguration {
+            configuration,
+            sources,
+        })
+    }
+}
+
+fn read_file(path: &Path) -> Result<Map<String, Value>> {
+    let describe = |err: &dyn fmt::Display| {
+        Error::from(ErrorKind::Configuration(format!(
+            "{}: {}",
+            path.display(),
+            err
+        )))
+    };
+    let input = fs::read_to_string(path).map_err(|err| describe(&err))?;
+    let value: Value = if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        serde_json::from_str(&input).map_err(|err| describe(&err))?
+    } else {
+        toml::from_str(&input).map_err(|err| describe(&err))?
+    };
+    match value {
+        Value::Object(map) => Ok(map),
+        _ => Err(describe(&"expected a table")),
+    }
+}
+
+/// Override `fields` with `overrides`, checking each value deserializes.
+fn merge(
+    fields: &mut Map<String, Value>,
+    sources: &mut BTreeMap<String, Source>,
+    overrides: Map<String, Value>,
+    source: Source,
+) -> Result<()> {
+    for (key, value) in overrides {
+        let key = camel_case(&key);
+        if !fields.contains_key(&key) {
+            warn!("ignoring unknown configuration field {} in {}", key, source);
+            continue;
+        }
+        if !is_valid(&key, &value) {
+            bail!(ErrorKind::Configuration(format!(
+                "invalid value for {} in {}",
+                snake_case(&key),
+                source
+            )));
+        }
+        sources.insert(snake_case(&key), source.clone());
+        fields.insert(key, value);
+    }
+    Ok(())
+}
+
+/// The fields set by environment variables, with the variable name and the
+/// parsed value.
+fn env_fields<E: Fn(&str) -> Option<String>>(
+    fields: &Map<String, Value>,
+    env: &E,
+) -> Result<BTreeMap<String, (String, Value)>> {
+    let mut found = BTreeMap::new();
+    for key in fields.keys() {
+        let field = snake_case(key);
+        let canonical = format!("{}{}", ENV_PREFIX, field.to_uppercase());
+        let aliases = ENV_ALIASES
+            .iter()
+            .filter(|(aliased, _)| *aliased == field)
+            .map(|(_, name)| name.to_string());
+        let set = std::iter::once(canonical)
+            .chain(aliases)
+            .find_map(|name| env(&name).map(|raw| (name, raw)));
+        if let Some((name, raw)) = set {
+            let value = parse_env(key, &raw).ok_or_else(|| {
+                ErrorKind::Configuration(format!("invalid value for {} in ${}", field, name))
+            })?;
+            found.insert(key.clone(), (name, value));
+        }
+    }
+    Ok(found)
+}
+
+/// Read `raw` as JSON, a plain string or a comma separated list, whichever
+/// suits the field.
+fn parse_env(key: &str, raw: &str) -> Option<Value> {
+    let list = raw
+        .split(',')
+        .map(|part| Value::from(part.trim()))
+        .filter(|part| part != "")
+        .collect();
+    serde_json::from_str(raw)
+        .ok()
+        .into_iter()
+        .chain([Value::from(raw), Value::Array(list)])
+        .find(|value| is_valid(key, value))
+}
+
+fn is_valid(key: &str, value: &Value) -> bool {
+    let mut single = Map::new();
+    single.insert(key.to_owned(), value.clone());
+    serde_json::from_value::<Configuration>(Value::Object(single)).is_ok()
+}
+
+fn camel_case(key: &str) -> String {
+    let mut parts = key.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+fn snake_case(key: &str) -> String {
+    let mut snake = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Level;
+    use std::collections::HashMap;
+
+    const FILE: &str = r#"
+access_token = "from-file"
+environment = "staging"
+itemsPerMinute = 10
+scrub_fields = ["password"]
+
+[profile.production]
+items_per_minute = 100
+host = "prod-1"
+"#;
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn load(loader: ConfigLoader, vars: &[(&str, &str)]) -> Result<LoadedConfiguration> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        loader.load_with(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_layers_and_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "rollbar.toml", FILE);
+
+        let loaded = load(
+            ConfigLoader::new().file(&path),
+            &[
+                ("ROLLBAR_ENVIRONMENT", "production"),
+                ("ROLLBAR_LOG_LEVEL", "warning"),
+                ("ROLLBAR_TIMEOUT", "3"),
+                ("ROLLBAR_SCRUB_FIELDS", "secret, token"),
+            ],
+        )
+        .unwrap();
+        let conf = &loaded.configuration;
+
+        assert_eq!(conf.access_token.as_deref(), Some("from-file"));
+        assert_eq!(conf.environment.as_deref(), Some("production"));
+        assert_eq!(conf.items_per_minute, 100);
+        assert_eq!(conf.host.as_deref(), Some("prod-1"));
+        assert_eq!(conf.log_level, Level::Warning);
+        assert_eq!(conf.timeout, 3);
+        assert_eq!(conf.scrub_fields, vec!["secret", "token"]);
+
+        assert_eq!(loaded.source("access_token"), Some(&Source::File(path)));
+        assert_eq!(
+            loaded.source("items_per_minute"),
+            Some(&Source::Profile("production".to_owned()))
+        );
+        assert_eq!(
+            loaded.source("timeout"),
+            Some(&Source::Env("ROLLBAR_TIMEOUT".to_owned()))
+        );
+        assert_eq!(loaded.source("max_retries"), Some(&Source::Default));
+    }
+
+    #[test]
+    fn test_profile_from_file_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "rollbar.toml", FILE);
+
+        let staging = load(ConfigLoader::new().file(&path), &[]).unwrap();
+        assert_eq!(staging.configuration.items_per_minute, 10);
+
+        let production = load(
+            ConfigLoader::new().file(&path),
+            &[("ROLLBAR_PROFILE", "production")],
+        )
+        .unwrap();
+        assert_eq!(production.configuration.items_per_minute, 100);
+    }
+
+    #[test]
+    fn test_json_file_and_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "rollbar.json",
+            r#"{"codeVersion": "abc", "sampleRates": {"debug": 0.5}}"#,
+        );
+
+        let loaded = load(
+            ConfigLoader::new().file(&path),
+            &[("ROLLBAR_TOKEN", "legacy"), ("ROLLBAR_CODE_VERSION", "123")],
+        )
+        .unwrap();
+
+        assert_eq!(loaded.configuration.access_token.as_deref(), Some("legacy"));
+        assert_eq!(loaded.configuration.code_version.as_deref(), Some("123"));
+        assert_eq!(loaded.configuration.sample_rates[&Level::Debug], 0.5);
+    }
+
+    #[test]
+    fn test_missing_file_is_skipped() {
+        let loaded = load(ConfigLoader::new().file("/nonexistent/rollbar.toml"), &[]).unwrap();
+        assert_eq!(
+            loaded.configuration.endpoint,
+            Configuration::default().endpoint
+        );
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "rollbar.toml", "timeout = \"soon\"");
+
+        assert!(load(ConfigLoader::new().file(&path), &[]).is_err());
+        assert!(load(ConfigLoader::new(), &[("ROLLBAR_QUEUE_DEPTH", "lots")]).is_err());
+    }
+}
@@ -9,5 +9,6 @@ lazy_static! {
     pub static ref NOTIFIER: Notifier = Notifier {
         name: Some("rollbar-rust".into()),
         version: Some(VERSION.into()),
+        diagnostic: None,
     };
 }
@@ -0,0 +1,185 @@
+use reqwest::Method;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::api::Api;
+use crate::configuration::Configuration;
+use crate::errors;
+use crate::transport::SendError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeployStatus {
+    Started,
+    Succeeded,
+    Failed,
+    TimedOut,
+}
+
+impl fmt::Display for DeployStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            DeployStatus::Started => "started",
+            DeployStatus::Succeeded => "succeeded",
+            DeployStatus::Failed => "failed",
+            DeployStatus::TimedOut => "timed_out",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl FromStr for DeployStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "started" => Ok(DeployStatus::Started),
+            "succeeded" => Ok(DeployStatus::Succeeded),
+            "failed" => Ok(DeployStatus::Failed),
+            "timed_out" => Ok(DeployStatus::TimedOut),
+            _ => Err(format!("unknown deploy status {:?}", s)),
+        }
+    }
+}
+
+/// A deploy of `revision` to `environment`.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
+pub struct Deploy {
+    pub environment: String,
+    pub revision: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollbar_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Without one, the API records the deploy as succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<DeployStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Created {
+    data: CreatedDeploy,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedDeploy {
+    deploy_id: u64,
+}
+
+/// Records deploys with the deploy API, using the access token, endpoint,
+/// timeout and proxy from a `Configuration`. The token needs the
+/// `post_server_item` scope.
+///
+/// The futures it returns must be run within a Tokio runtime.
+#[derive(Debug, Clone)]
+pub struct DeployClient {
+    api: Api,
+}
+
+impl DeployClient {
+    pub fn new(configuration: &Configuration) -> errors::Result<Self> {
+        Ok(DeployClient {
+            api: Api::new(configuration)?,
+        })
+    }
+
+    /// Record `deploy`, returning the id the API assigned to it.
+    pub async fn report(&self, deploy: &Deploy) -> Result<u64, SendError> {
+        let request = self.api.request(Method::POST, "deploy")?.json(deploy);
+        let created: Created = Api::send(request).await?;
+        Ok(created.data.deploy_id)
+    }
+
+    /// Change the status of a deploy, e.g. once one that was reported as
+    /// started has finished.
+    pub async fn update(&self, deploy_id: u64, status: DeployStatus) -> Result<(), SendError> {
+        let request = self
+            .api
+            .request(Method::PATCH, &format!("deploy/{}", deploy_id))?
+            .json(&serde_json::json!({ "status": status }));
+        let _: Value = Api::send(request).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::stand_in::{self, StandIn};
+    use tokio::runtime::Runtime;
+
+    fn client(server: &StandIn) -> DeployClient {
+        DeployClient::new(&Configuration {
+            endpoint: server.url.clone(),
+            access_token: Some("abc123".to_owned()),
+            ..Configuration::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_report_and_update() {
+        let server = StandIn::start(vec![
+            (200, vec![], r#"{"data":{"deploy_id":1234}}"#.to_owned()),
+            (200, vec![], r#"{"err":0,"result":{"id":1234}}"#.to_owned()),
+        ]);
+        let client = client(&server);
+        let deploy = Deploy::builder()
+            .environment("production")
+            .revision("3f2a9c1")
+            .local_username("ci")
+            .status(DeployStatus::Started)
+            .build();
+
+        let runtime = Runtime::new().unwrap();
+        let deploy_id = runtime.block_on(client.report(&deploy)).unwrap();
+        assert_eq!(deploy_id, 1234);
+        runtime
+            .block_on(client.update(deploy_id, DeployStatus::Succeeded))
+            .unwrap();
+
+        assert_eq!(
+            server.request_lines(),
+            vec!["POST /api/1/deploy", "PATCH /api/1/deploy/1234"]
+        );
+        let requests = server.requests();
+        let reported: Value = serde_json::from_str(&requests[0]).unwrap();
+        assert_eq!(
+            reported,
+            serde_json::json!({
+                "environment": "production",
+                "revision": "3f2a9c1",
+                "local_username": "ci",
+                "status": "started",
+            })
+        );
+        assert_eq!(requests[1], r#"{"status":"succeeded"}"#);
+    }
+
+    #[test]
+    fn test_reports_api_errors() {
+        let server = StandIn::start(vec![stand_in::error(403, "insufficient scope")]);
+        let deploy = Deploy::builder()
+            .environment("production")
+            .revision("3f2a9c1")
+            .build();
+
+        let result = Runtime::new()
+            .unwrap()
+            .block_on(client(&server).report(&deploy));
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "HTTP 403: insufficient scope"
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!("timed_out".parse(), Ok(DeployStatus::TimedOut));
+        assert!("done".parse::<DeployStatus>().is_err());
+    }
+}
@@ -1 +1,22 @@
-error_chain! {}
+error_chain! {
+    foreign_links {
+        InvalidConfiguration(crate::configuration::ConfigurationError);
+    }
+
+    errors {
+        MissingAccessToken {
+            description("access_token is missing")
+            display("configuration is missing access_token")
+        }
+
+        Transport(s: String) {
+            description("transport failure")
+            display("could not create transport: '{}'", s)
+        }
+
+        Configuration(s: String) {
+            description("configuration failure")
+            display("could not load configuration: '{}'", s)
+        }
+    }
+}
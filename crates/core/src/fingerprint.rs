@@ -0,0 +1,318 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::configuration::{Configuration, FingerprintPart, GroupingRule};
+use crate::hooks::class_matches;
+use crate::types::*;
+
+/// The longest title the API accepts.
+const MAX_TITLE_LEN: usize = 255;
+
+lazy_static! {
+    static ref UUID: Regex =
+        Regex::new(r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b")
+            .unwrap();
+    static ref HEX: Regex = Regex::new(r"(?i)\b0x[0-9a-f]+\b|\b[0-9a-f]{8,}\b").unwrap();
+    static ref NUMBER: Regex = Regex::new(r"\d+").unwrap();
+}
+
+/// Replace the parts of a message that vary between otherwise identical
+/// occurrences: UUIDs, hex strings and numbers.
+pub fn normalize_message(message: &str) -> String {
+    let message = UUID.replace_all(message, "<uuid>");
+    let message = HEX.replace_all(&message, "<hex>");
+    NUMBER.replace_all(&message, "<n>").into_owned()
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    exception_class: Option<String>,
+    message: Option<Regex>,
+    fingerprint: Vec<FingerprintPart>,
+    title: Option<String>,
+}
+
+impl Rule {
+    fn matches(&self, class: Option<&str>, message: Option<&str>) -> bool {
+        let class_ok = match (&self.exception_class, class) {
+            (None, _) => true,
+            (Some(pattern), Some(class)) => class_matches(pattern, class),
+            (Some(_), None) => false,
+        };
+        let message_ok = match (&self.message, message) {
+            (None, _) => true,
+            (Some(regex), Some(message)) => regex.is_match(message),
+            (Some(_), None) => false,
+        };
+        class_ok && message_ok
+    }
+}
+
+/// Fills in `data.fingerprint` and `data.title` from the first matching
+/// `grouping_rules` entry, so that occurrences of the same problem group
+/// together no matter which notifier sent them. Values set by the caller
+/// are left alone.
+#[derive(Debug, Clone)]
+pub struct Fingerprinter {
+    rules: Vec<Rule>,
+    in_app_prefixes: Vec<String>,
+    frames: usize,
+}
+
+impl Fingerprinter {
+    pub fn new(configuration: &Configuration) -> Self {
+        let rules = configuration
+            .grouping_rules
+            .iter()
+            .filter_map(|rule: &GroupingRule| {
+                let message = match rule.message.as_deref().map(Regex::new) {
+                    None => None,
+                    Some(Ok(regex)) => Some(regex),
+                    Some(Err(err)) => {
+                        warn!(
+                            "ignoring grouping rule with invalid message pattern: {}",
+                            err
+                        );
+                        return None;
+                    }
+                };
+                Some(Rule {
+                    exception_class: rule.exception_class.clone(),
+                    message,
+                    fingerprint: rule.fingerprint.clone(),
+                    title: rule.title.clone(),
+                })
+            })
+            .collect();
+
+        Fingerprinter {
+            rules,
+            in_app_prefixes: configuration.in_app_prefixes.clone(),
+            frames: configuration.fingerprint_frames,
+        }
+    }
+
+    pub fn fingerprint_item(&self, item: &mut Item) {
+        let data = &mut item.data;
+        if data.fingerprint.is_some() && data.title.is_some() {
+            return;
+        }
+        let class = data.body.exception_classes().first().map(|c| c.to_string());
+        let message = data.body.message().map(str::to_owned);
+        let rule = match self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(class.as_deref(), message.as_deref()))
+        {
+            Some(rule) => rule,
+            None => return,
+        };
+
+        if data.fingerprint.is_none() && !rule.fingerprint.is_empty() {
+            let parts: Vec<String> = rule
+                .fingerprint
+                .iter()
+                .map(|part| match part {
+                    FingerprintPart::Class => class.clone().unwrap_or_default(),
+                    FingerprintPart::Message => message
+                        .as_deref()
+                        .map(normalize_message)
+                        .unwrap_or_default(),
+                    FingerprintPart::Frames => self.frames(&data.body).join("|"),
+                })
+                .collect();
+            data.fingerprint = Some(format!("{:016x}", fnv1a(parts.join("\n").as_bytes())));
+        }
+
+        if data.title.is_none() {
+            data.title = rule
+                .title
+                .as_deref()
+                .and_then(|template| title(template, class.as_deref(), message.as_deref()))
+                .map(|title| title.chars().take(MAX_TITLE_LEN).collect());
+        }
+    }
+
+    /// The top in-app frames of the outermost trace, most recent call first,
+    /// without line numbers so that unrelated edits do not split groups.
+    fn frames(&self, body: &Body) -> Vec<String> {
+        let frames = match body {
+            Body::TraceBody { trace, .. } => &trace.frames,
+            Body::TraceChainBody { trace_chain, .. } => match trace_chain.first() {
+                Some(trace) => &trace.frames,
+                None => return vec![],
+            },
+            _ => return vec![],
+        };
+        frames
+            .iter()
+            .rev()
+            .map(frame_name)
+            .filter(|name| self.is_in_app(name))
+            .take(self.frames)
+            .collect()
+    }
+
+    fn is_in_app(&self, name: &str) -> bool {
+        self.in_app_prefixes.is_empty()
+            || self
+                .in_app_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+}
+
+fn frame_name(frame: &Frame) -> String {
+    match (&frame.class_name, &frame.method) {
+        (Some(class), Some(method)) => format!("{}.{}", class, method),
+        (None, Some(method)) => method.clone(),
+        _ => frame.filename.clone(),
+    }
+}
+
+/// Fill in `{class}` and `{message}` in `template`. There is no title when
+/// the template needs a part the item does not have.
+fn title(template: &str, class: Option<&str>, message: Option<&str>) -> Option<String> {
+    let mut title = template.to_owned();
+    for (placeholder, value) in [("{class}", class), ("{message}", message)] {
+        if title.contains(placeholder) {
+            title = title.replace(placeholder, value?);
+        }
+    }
+    Some(title)
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across processes,
+/// platforms and languages.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprinter(rules: Vec<GroupingRule>) -> Fingerprinter {
+        Fingerprinter::new(&Configuration {
+            grouping_rules: rules,
+            in_app_prefixes: vec!["com.example.".to_owned()],
+            fingerprint_frames: 2,
+            ..Configuration::default()
+        })
+    }
+
+    fn frame(class: &str, method: &str, lineno: i32) -> Frame {
+        Frame::builder()
+            .filename("Main.java")
+            .class_name(class)
+            .method(method)
+            .lineno(lineno)
+            .build()
+    }
+
+    fn trace_item(class: &str, message: &str, frames: Vec<Frame>) -> Item {
+        let exception = Exception::builder().class(class).message(message).build();
+        let trace = Trace::builder().frames(frames).exception(exception).build();
+        let data = Data::builder()
+            .body(Body::builder().trace(trace).build())
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    #[test]
+    fn test_normalize_message() {
+        assert_eq!(
+            normalize_message(
+                "user 42 not found in 123e4567-e89b-12d3-a456-426614174000 at 0x7ffd5fbff8 (deadbeef00)"
+            ),
+            "user <n> not found in <uuid> at <hex> (<hex>)"
+        );
+    }
+
+    #[test]
+    fn test_groups_by_class_message_and_in_app_frames() {
+        let fingerprinter = fingerprinter(vec![GroupingRule::default()]);
+        let frames = |lineno| {
+            vec![
+                frame("com.example.Main", "main", lineno),
+                frame("com.example.Orders", "place", lineno),
+                frame("java.util.HashMap", "get", 1),
+            ]
+        };
+
+        let mut first = trace_item(
+            "java.lang.IllegalStateException",
+            "order 17 failed",
+            frames(10),
+        );
+        let mut second = trace_item(
+            "java.lang.IllegalStateException",
+            "order 99 failed",
+            frames(20),
+        );
+        let mut other = trace_item(
+            "java.lang.IllegalStateException",
+            "order 17 rejected",
+            frames(10),
+        );
+        for item in [&mut first, &mut second, &mut other] {
+            fingerprinter.fingerprint_item(item);
+        }
+
+        assert!(first.data.fingerprint.is_some());
+        assert_eq!(first.data.fingerprint, second.data.fingerprint);
+        assert_ne!(first.data.fingerprint, other.data.fingerprint);
+        assert_eq!(
+            first.data.title.as_deref(),
+            Some("java.lang.IllegalStateException: order 17 failed")
+        );
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let fingerprinter = fingerprinter(vec![
+            GroupingRule {
+                exception_class: Some("java.net.".to_owned()),
+                fingerprint: vec![FingerprintPart::Class],
+                title: Some("Network: {class}".to_owned()),
+                ..GroupingRule::default()
+            },
+            GroupingRule::default(),
+        ]);
+
+        let mut timeout = trace_item("java.net.SocketTimeoutException", "read timed out", vec![]);
+        let mut refused = trace_item(
+            "java.net.SocketTimeoutException",
+            "connect timed out",
+            vec![],
+        );
+        fingerprinter.fingerprint_item(&mut timeout);
+        fingerprinter.fingerprint_item(&mut refused);
+
+        assert_eq!(timeout.data.fingerprint, refused.data.fingerprint);
+        assert_eq!(
+            timeout.data.title.as_deref(),
+            Some("Network: java.net.SocketTimeoutException")
+        );
+    }
+
+    #[test]
+    fn test_keeps_caller_values() {
+        let fingerprinter = fingerprinter(vec![GroupingRule::default()]);
+        let mut item = trace_item("Error", "boom", vec![]);
+        item.data.fingerprint = Some("mine".to_owned());
+
+        fingerprinter.fingerprint_item(&mut item);
+
+        assert_eq!(item.data.fingerprint.as_deref(), Some("mine"));
+        assert_eq!(item.data.title.as_deref(), Some("Error: boom"));
+    }
+
+    #[test]
+    fn test_title_needs_its_parts() {
+        assert_eq!(title("{class}: {message}", None, Some("hi")), None);
+        assert_eq!(title("{message}", None, Some("hi")).as_deref(), Some("hi"));
+    }
+}
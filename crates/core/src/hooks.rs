@@ -0,0 +1,276 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::configuration::Configuration;
+use crate::transport::Transport;
+use crate::types::{Item, Level};
+
+/// What to do with an item once a `BeforeSend` hook has seen it.
+pub enum Decision {
+    /// Hand the item to the next hook, and then to the transport.
+    Send,
+    /// Do not send the item.
+    Drop,
+    /// Send the item with this transport instead, skipping any later hooks.
+    Route(Arc<dyn Transport>),
+}
+
+impl fmt::Debug for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Decision::Send => write!(f, "Send"),
+            Decision::Drop => write!(f, "Drop"),
+            Decision::Route(_) => write!(f, "Route"),
+        }
+    }
+}
+
+/// Inspects, and may modify, drop or re-route, every item before it is handed
+/// to the transport.
+///
+/// Implemented for closures, so a hook can be as simple as:
+///
+/// ```
+/// use rollbar_rust::{Configuration, Decision, Item, Rollbar};
+///
+/// let configuration = Configuration {
+///     access_token: Some("abc123".to_owned()),
+///     ..Configuration::default()
+/// };
+/// let rollbar = Rollbar::new(configuration)
+///     .unwrap()
+///     .with_before_send(|item: &mut Item| {
+///         item.data.context = Some("checkout".to_owned());
+///         Decision::Send
+///     });
+/// ```
+pub trait BeforeSend: Send + Sync + 'static {
+    fn before_send(&self, item: &mut Item) -> Decision;
+}
+
+impl<F> BeforeSend for F
+where
+    F: Fn(&mut Item) -> Decision + Send + Sync + 'static,
+{
+    fn before_send(&self, item: &mut Item) -> Decision {
+        self(item)
+    }
+}
+
+/// Drops items whose outermost exception is of an ignored class. An entry
+/// ending in `.` ignores every class in that package.
+#[derive(Debug, Clone)]
+pub struct IgnoreExceptionClasses {
+    classes: Vec<String>,
+}
+
+impl IgnoreExceptionClasses {
+    pub fn new<I, S>(classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        IgnoreExceptionClasses {
+            classes: classes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn is_ignored(&self, class: &str) -> bool {
+        self.classes
+            .iter()
+            .any(|ignored| class_matches(ignored, class))
+    }
+}
+
+/// Whether `class` is `pattern`, or is in the package `pattern` if it ends
+/// in `.`.
+pub(crate) fn class_matches(pattern: &str, class: &str) -> bool {
+    class == pattern || (pattern.ends_with('.') && class.starts_with(pattern))
+}
+
+impl BeforeSend for IgnoreExceptionClasses {
+    fn before_send(&self, item: &mut Item) -> Decision {
+        match item.data.body.exception_classes().first() {
+            Some(class) if self.is_ignored(class) => Decision::Drop,
+            _ => Decision::Send,
+        }
+    }
+}
+
+/// Drops items whose message, or exception message for traces, matches any
+/// of the patterns.
+#[derive(Debug, Clone)]
+pub struct IgnoreMessages {
+    patterns: Vec<Regex>,
+}
+
+impl IgnoreMessages {
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        IgnoreMessages { patterns }
+    }
+}
+
+impl BeforeSend for IgnoreMessages {
+    fn before_send(&self, item: &mut Item) -> Decision {
+        match item.data.body.message() {
+            Some(message) if self.patterns.iter().any(|p| p.is_match(message)) => Decision::Drop,
+            _ => Decision::Send,
+        }
+    }
+}
+
+/// Drops items below a level. Items without a level count as errors.
+#[derive(Debug, Clone)]
+pub struct MinimumLevel(pub Level);
+
+impl BeforeSend for MinimumLevel {
+    fn before_send(&self, item: &mut Item) -> Decision {
+        if item.data.level.clone().unwrap_or_default() < self.0 {
+            Decision::Drop
+        } else {
+            Decision::Send
+        }
+    }
+}
+
+/// The hooks a `Rollbar` runs, in order: the stages configured in
+/// `Configuration` first, then any added with `Rollbar::with_before_send`.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    hooks: Vec<Box<dyn BeforeSend>>,
+}
+
+impl Hooks {
+    pub(crate) fn new(configuration: &Configuration) -> Self {
+        let mut hooks = Hooks::default();
+        if let Some(level) = &configuration.minimum_level {
+            hooks.push(MinimumLevel(level.clone()));
+        }
+        if !configuration.ignored_exception_classes.is_empty() {
+            hooks.push(IgnoreExceptionClasses::new(
+                configuration.ignored_exception_classes.iter().cloned(),
+            ));
+        }
+        let patterns: Vec<Regex> = configuration
+            .ignored_messages
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    warn!(
+                        "ignoring invalid ignored message pattern {}: {}",
+                        pattern, err
+                    );
+                    None
+                }
+            })
+            .collect();
+        if !patterns.is_empty() {
+            hooks.push(IgnoreMessages::new(patterns));
+        }
+        hooks
+    }
+
+    pub(crate) fn push<H: BeforeSend>(&mut self, hook: H) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Run the hooks until one of them decides to drop or re-route `item`.
+    pub(crate) fn run(&self, item: &mut Item) -> Decision {
+        for hook in &self.hooks {
+            match hook.before_send(item) {
+                Decision::Send => {}
+                decision => return decision,
+            }
+        }
+        Decision::Send
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hooks({})", self.hooks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Body, Data, Exception, Message, Trace};
+
+    fn message(level: Level, body: &str) -> Item {
+        let message = Message::builder().body(body).build();
+        let data = Data::builder()
+            .body(Body::builder().message(message).build())
+            .level(level)
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    fn exception(class: &str) -> Item {
+        let trace = Trace::builder()
+            .exception(Exception::builder().class(class).build())
+            .build();
+        let data = Data::builder()
+            .body(Body::builder().trace(trace).build())
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    fn is_dropped(hooks: &Hooks, mut item: Item) -> bool {
+        matches!(hooks.run(&mut item), Decision::Drop)
+    }
+
+    #[test]
+    fn test_configured_stages() {
+        let hooks = Hooks::new(&Configuration {
+            minimum_level: Some(Level::Info),
+            ignored_exception_classes: vec![
+                "java.io.IOException".to_owned(),
+                "javax.naming.".to_owned(),
+            ],
+            ignored_messages: vec!["^health check".to_owned(), "(".to_owned()],
+            ..Configuration::default()
+        });
+
+        assert!(is_dropped(&hooks, message(Level::Debug, "starting")));
+        assert!(!is_dropped(&hooks, message(Level::Info, "starting")));
+        assert!(is_dropped(
+            &hooks,
+            message(Level::Error, "health check failed")
+        ));
+        assert!(!is_dropped(
+            &hooks,
+            message(Level::Error, "failed health check")
+        ));
+        assert!(is_dropped(&hooks, exception("java.io.IOException")));
+        assert!(is_dropped(
+            &hooks,
+            exception("javax.naming.NamingException")
+        ));
+        assert!(!is_dropped(&hooks, exception("java.io.IOExceptionWrapper")));
+        assert!(!is_dropped(
+            &hooks,
+            exception("java.lang.NullPointerException")
+        ));
+    }
+
+    #[test]
+    fn test_hooks_run_in_order() {
+        let mut hooks = Hooks::default();
+        hooks.push(|item: &mut Item| {
+            item.data.context = Some("first".to_owned());
+            Decision::Send
+        });
+        hooks.push(|_: &mut Item| Decision::Drop);
+        hooks.push(|item: &mut Item| {
+            item.data.context = Some("never".to_owned());
+            Decision::Send
+        });
+
+        let mut item = message(Level::Info, "hello");
+        assert!(matches!(hooks.run(&mut item), Decision::Drop));
+        assert_eq!(item.data.context.as_deref(), Some("first"));
+    }
+}
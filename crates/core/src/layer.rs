@@ -0,0 +1,289 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::client::Rollbar;
+use crate::logger::{matches_target, IGNORED_TARGETS};
+use crate::types::*;
+
+/// A `tracing_subscriber::Layer` that reports `ERROR` and `WARN` events as
+/// message items. The fields of the current span stack end up in
+/// `Data.custom`, and lower level events are recorded as telemetry in the
+/// client's buffer so they are attached to the next reported item.
+pub struct RollbarLayer {
+    rollbar: Arc<Rollbar>,
+}
+
+/// The recorded fields of a span, stored in the span's extensions.
+struct SpanFields(Map<String, Value>);
+
+impl RollbarLayer {
+    pub fn new(rollbar: Arc<Rollbar>) -> Self {
+        RollbarLayer { rollbar }
+    }
+
+    fn push_breadcrumb(&self, level: Level, event: &Event, visitor: FieldVisitor) {
+        let mut telemetry = Telemetry::log(level, visitor.message);
+        telemetry.body.extend(visitor.fields);
+        telemetry
+            .body
+            .insert("target".to_owned(), Value::from(event.metadata().target()));
+        self.rollbar.push_telemetry(telemetry);
+    }
+
+    fn report(&self, level: Level, event: &Event, visitor: FieldVisitor, spans: Vec<Value>) {
+        let metadata = event.metadata();
+        let mut extra = visitor.fields;
+        extra.insert("target".to_owned(), Value::from(metadata.target()));
+        if let Some(module_path) = metadata.module_path() {
+            extra.insert("module_path".to_owned(), Value::from(module_path));
+        }
+        if let Some(file) = metadata.file() {
+            extra.insert("file".to_owned(), Value::from(file));
+        }
+        if let Some(line) = metadata.line() {
+            extra.insert("line".to_owned(), Value::from(line));
+        }
+
+        let message = Message::builder()
+            .body(visitor.message)
+            .extra(extra)
+            .build();
+        let body = Body::builder().message(message).build();
+
+        let mut custom = HashMap::new();
+        if let Some(span) = spans.last() {
+            custom.insert("span".to_owned(), span.clone());
+        }
+        if !spans.is_empty() {
+            custom.insert("spans".to_owned(), Value::Array(spans));
+        }
+
+        let data = Data::builder()
+            .body(body)
+            .level(level)
+            .maybe_custom(Some(custom).filter(|c| !c.is_empty()))
+            .build();
+        if let Err(err) = self.rollbar.send(data) {
+            debug!("could not report tracing event: {}", err);
+        }
+    }
+}
+
+impl<S> Layer<S> for RollbarLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut visitor = FieldVisitor::default();
+            attrs.record(&mut visitor);
+            span.extensions_mut()
+                .insert(SpanFields(visitor.fields.into_iter().collect()));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut visitor = FieldVisitor::default();
+            values.record(&mut visitor);
+            if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+                fields.0.extend(visitor.fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if IGNORED_TARGETS
+            .iter()
+            .any(|t| matches_target(t, metadata.target()))
+        {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let level = level_from_tracing(metadata.level());
+
+        if *metadata.level() > tracing::Level::WARN {
+            self.push_breadcrumb(level, event, visitor);
+            return;
+        }
+
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| {
+                        let fields = span
+                            .extensions()
+                            .get::<SpanFields>()
+                            .map(|f| f.0.clone())
+                            .unwrap_or_default();
+                        let mut value = Map::new();
+                        value.insert("name".to_owned(), Value::from(span.name()));
+                        value.insert("fields".to_owned(), Value::Object(fields));
+                        Value::Object(value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.report(level, event, visitor, spans);
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: HashMap<String, Value>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields
+            .insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields
+            .insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields
+            .insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields
+            .insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_owned();
+        } else {
+            self.fields
+                .insert(field.name().to_owned(), Value::from(value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .insert(field.name().to_owned(), Value::from(format!("{:?}", value)));
+        }
+    }
+}
+
+fn level_from_tracing(level: &tracing::Level) -> Level {
+    match *level {
+        tracing::Level::ERROR => Level::Error,
+        tracing::Level::WARN => Level::Warning,
+        tracing::Level::INFO => Level::Info,
+        _ => Level::Debug,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_from_tracing() {
+        assert_eq!(level_from_tracing(&tracing::Level::ERROR), Level::Error);
+        assert_eq!(level_from_tracing(&tracing::Level::WARN), Level::Warning);
+        assert_eq!(level_from_tracing(&tracing::Level::TRACE), Level::Debug);
+    }
+
+    #[cfg(feature = "testing")]
+    mod events {
+        use super::*;
+        use crate::configuration::Configuration;
+        use crate::transport::MemoryTransport;
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::Registry;
+
+        fn with_layer<F: FnOnce()>(f: F) -> (Arc<Rollbar>, MemoryTransport) {
+            let transport = MemoryTransport::new(Configuration {
+                access_token: Some("abc123".to_owned()),
+                ..Configuration::default()
+            });
+            let rollbar = Arc::new(Rollbar::with_transport(transport.clone()));
+            let subscriber = Registry::default().with(RollbarLayer::new(rollbar.clone()));
+            tracing::subscriber::with_default(subscriber, f);
+            (rollbar, transport)
+        }
+
+        // Events from this crate are ignored, so the tests use their own target.
+        #[test]
+        fn test_events_become_items() {
+            let (_, transport) = with_layer(|| {
+                tracing::error!(target: "my_app", order_id = 7, "payment declined");
+                tracing::warn!(target: "my_app", "retrying");
+            });
+
+            transport.assert_count(2);
+            transport.assert_message(Level::Error, "payment declined");
+            transport.assert_message(Level::Warning, "retrying");
+            match &transport.with_level(Level::Error)[0].data.body {
+                Body::MessageBody { message, .. } => {
+                    assert_eq!(message.extra["order_id"], 7);
+                    assert_eq!(message.extra["target"], "my_app");
+                }
+                _ => panic!("expected a message body"),
+            }
+        }
+
+        #[test]
+        fn test_span_fields_end_up_in_custom() {
+            let (_, transport) = with_layer(|| {
+                let outer = tracing::info_span!(target: "my_app", "request", path = "/checkout");
+                let _outer = outer.enter();
+                let inner = tracing::info_span!(
+                    target: "my_app",
+                    "charge",
+                    attempt = tracing::field::Empty
+                );
+                inner.record("attempt", &2);
+                let _inner = inner.enter();
+                tracing::error!(target: "my_app", "card expired");
+            });
+
+            let custom = transport.items()[0].data.custom.clone().unwrap();
+            assert_eq!(
+                custom["span"],
+                serde_json::json!({ "name": "charge", "fields": { "attempt": 2 } })
+            );
+            assert_eq!(custom["spans"][0]["name"], "request");
+            assert_eq!(custom["spans"][0]["fields"]["path"], "/checkout");
+        }
+
+        #[test]
+        fn test_lower_level_events_are_breadcrumbs() {
+            let (rollbar, transport) = with_layer(|| {
+                tracing::info!(target: "my_app", user = "ada", "signed in");
+                tracing::debug!(target: "my_app", "loading cart");
+            });
+
+            transport.assert_empty();
+            let telemetry = rollbar.telemetry().snapshot();
+            assert_eq!(telemetry.len(), 2);
+            assert_eq!(telemetry[0].level, Level::Info);
+            assert_eq!(telemetry[0].body["message"], "signed in");
+            assert_eq!(telemetry[0].body["user"], "ada");
+            assert_eq!(telemetry[1].level, Level::Debug);
+        }
+    }
+}
@@ -7,16 +7,62 @@ extern crate builder_derive;
 #[macro_use]
 extern crate error_chain;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod api;
+mod client;
 pub mod configuration;
-mod errors;
+#[cfg(not(target_arch = "wasm32"))]
+mod deploy;
+pub mod errors;
+mod fingerprint;
+mod hooks;
+#[cfg(feature = "tracing")]
+mod layer;
+mod logger;
+pub mod panic;
+#[cfg(not(target_arch = "wasm32"))]
+mod read;
+mod sample;
+mod scope;
+mod scrub;
+mod telemetry;
+mod trace;
 mod transport;
+mod truncate;
 
 pub mod constants;
 pub mod types;
 pub use log::Level;
 
-pub use crate::configuration::Configuration;
-pub use crate::transport::{HttpTransport, Transport};
+pub use crate::client::Rollbar;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::configuration::ConfigLoader;
+pub use crate::configuration::{
+    CaptureIp, Configuration, ConfigurationError, FingerprintPart, GroupingRule, OverflowPolicy,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::deploy::{Deploy, DeployClient, DeployStatus};
+pub use crate::fingerprint::{normalize_message, Fingerprinter};
+pub use crate::hooks::{
+    BeforeSend, Decision, IgnoreExceptionClasses, IgnoreMessages, MinimumLevel,
+};
+#[cfg(feature = "tracing")]
+pub use crate::layer::RollbarLayer;
+pub use crate::logger::RollbarLogger;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::read::{ItemQuery, ItemRecord, ItemStatus, Occurrence, ReadClient};
+pub use crate::sample::Sampler;
+pub use crate::scope::{Scope, ScopeGuard};
+pub use crate::scrub::Scrubber;
+pub use crate::telemetry::TelemetryBuffer;
+#[cfg(feature = "testing")]
+pub use crate::transport::{BodyKind, MemoryTransport};
+pub use crate::transport::{
+    DroppedItems, HttpTransport, SendError, SendFuture, Sent, Transport, TransportStats,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::transport::{FileTransport, StdoutTransport};
+pub use crate::truncate::Truncator;
 pub use crate::types::*;
 
 #[derive(Default)]
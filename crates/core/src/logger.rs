@@ -0,0 +1,294 @@
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::client::Rollbar;
+use crate::types::Level;
+
+/// Targets that are never forwarded, otherwise logging done while sending an
+/// item would itself produce items.
+pub(crate) const IGNORED_TARGETS: &[&str] = &["rollbar_rust", "reqwest", "hyper", "tokio", "mio"];
+
+/// A `log::Log` backend that reports records as message items.
+///
+/// Records are forwarded when they are at or above `Configuration.log_level`,
+/// unless a directive for a more specific target says otherwise. Directives
+/// use the `env_logger` syntax, e.g. `warn,my_app::db=debug,hyper=off`.
+pub struct RollbarLogger {
+    rollbar: Arc<Rollbar>,
+    default: LevelFilter,
+    directives: Vec<(String, LevelFilter)>,
+    inner: Option<Box<dyn Log>>,
+}
+
+impl RollbarLogger {
+    pub fn new(rollbar: Arc<Rollbar>) -> Self {
+        let default = level_filter(&rollbar.config().log_level);
+        RollbarLogger {
+            rollbar,
+            default,
+            directives: Vec::new(),
+            inner: None,
+        }
+    }
+
+    /// Apply `env_logger` style directives. A bare level replaces the
+    /// configured default; `target=level` entries override it per target.
+    pub fn directives(mut self, spec: &str) -> Self {
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => match LevelFilter::from_str(level.trim()) {
+                    Ok(level) => self.directives.push((target.trim().to_owned(), level)),
+                    Err(_) => warn!("ignoring invalid log directive: {}", directive),
+                },
+                None => match LevelFilter::from_str(directive) {
+                    Ok(level) => self.default = level,
+                    Err(_) => self
+                        .directives
+                        .push((directive.to_owned(), LevelFilter::Trace)),
+                },
+            }
+        }
+        // Longest target first so the most specific directive wins.
+        self.directives
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        self
+    }
+
+    /// Pass every record on to `inner` as well, so console output is kept.
+    pub fn wrap(mut self, inner: Box<dyn Log>) -> Self {
+        self.inner = Some(inner);
+        self
+    }
+
+    /// Install as the global logger.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let max_level = if self.inner.is_some() {
+            LevelFilter::Trace
+        } else {
+            self.directives
+                .iter()
+                .map(|(_, level)| *level)
+                .fold(self.default, std::cmp::max)
+        };
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+
+    fn filter_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .find(|(prefix, _)| matches_target(prefix, target))
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn forwards(&self, metadata: &Metadata) -> bool {
+        let target = metadata.target();
+        !IGNORED_TARGETS.iter().any(|t| matches_target(t, target))
+            && metadata.level() <= self.filter_for(target)
+    }
+}
+
+impl Log for RollbarLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.forwards(metadata)
+            || self
+                .inner
+                .as_ref()
+                .is_some_and(|inner| inner.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(inner) = &self.inner {
+            inner.log(record);
+        }
+        if !self.forwards(record.metadata()) {
+            return;
+        }
+
+        let mut extra = HashMap::new();
+        extra.insert("target".to_owned(), Value::from(record.target()));
+        if let Some(module_path) = record.module_path() {
+            extra.insert("module_path".to_owned(), Value::from(module_path));
+        }
+        if let Some(file) = record.file() {
+            extra.insert("file".to_owned(), Value::from(file));
+        }
+        if let Some(line) = record.line() {
+            extra.insert("line".to_owned(), Value::from(line));
+        }
+
+        let level = level_from_log(record.level());
+        let message = record.args().to_string();
+        if let Err(err) = self.rollbar.message_with_extra(level, message, extra) {
+            debug!("could not forward log record: {}", err);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
+
+fn level_from_log(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::Error,
+        log::Level::Warn => Level::Warning,
+        log::Level::Info => Level::Info,
+        log::Level::Debug | log::Level::Trace => Level::Debug,
+    }
+}
+
+fn level_filter(level: &Level) -> LevelFilter {
+    match level {
+        Level::Critical | Level::Error => LevelFilter::Error,
+        Level::Warning => LevelFilter::Warn,
+        Level::Info => LevelFilter::Info,
+        Level::Debug => LevelFilter::Debug,
+    }
+}
+
+pub(crate) fn matches_target(prefix: &str, target: &str) -> bool {
+    target == prefix
+        || target
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_target() {
+        assert!(matches_target("my_app", "my_app"));
+        assert!(matches_target("my_app", "my_app::db"));
+        assert!(!matches_target("my_app", "my_application"));
+        assert!(!matches_target("my_app::db", "my_app"));
+    }
+
+    #[test]
+    fn test_level_conversions() {
+        assert_eq!(level_from_log(log::Level::Warn), Level::Warning);
+        assert_eq!(level_from_log(log::Level::Trace), Level::Debug);
+        assert_eq!(level_filter(&Level::Critical), LevelFilter::Error);
+        assert_eq!(level_filter(&Level::Info), LevelFilter::Info);
+    }
+
+    #[cfg(feature = "testing")]
+    mod forwarding {
+        use super::*;
+        use crate::configuration::Configuration;
+        use crate::transport::MemoryTransport;
+        use crate::types::{Body, Item};
+        use std::sync::Mutex;
+
+        fn logger() -> (RollbarLogger, MemoryTransport) {
+            let transport = MemoryTransport::new(Configuration {
+                access_token: Some("abc123".to_owned()),
+                ..Configuration::default()
+            });
+            let rollbar = Rollbar::with_transport(transport.clone());
+            (RollbarLogger::new(Arc::new(rollbar)), transport)
+        }
+
+        fn log(logger: &RollbarLogger, level: log::Level, target: &str, message: &str) {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .module_path_static(Some("my_app::db"))
+                    .file_static(Some("src/db.rs"))
+                    .line(Some(42))
+                    .args(format_args!("{}", message))
+                    .build(),
+            );
+        }
+
+        fn extra(item: &Item) -> &HashMap<String, Value> {
+            match &item.data.body {
+                Body::MessageBody { message, .. } => &message.extra,
+                _ => panic!("expected a message body"),
+            }
+        }
+
+        #[test]
+        fn test_records_become_items() {
+            let (logger, transport) = logger();
+
+            log(&logger, log::Level::Warn, "my_app::db", "pool exhausted");
+
+            transport.assert_count(1);
+            transport.assert_message(Level::Warning, "pool exhausted");
+            let item = &transport.items()[0];
+            assert_eq!(extra(item)["target"], "my_app::db");
+            assert_eq!(extra(item)["module_path"], "my_app::db");
+            assert_eq!(extra(item)["file"], "src/db.rs");
+            assert_eq!(extra(item)["line"], 42);
+        }
+
+        #[test]
+        fn test_directives_filter_records() {
+            let (logger, transport) = logger();
+            let logger = logger.directives("warn,my_app::db=debug,my_app::db::pool=off");
+
+            log(
+                &logger,
+                log::Level::Info,
+                "my_app",
+                "skipped by the default",
+            );
+            log(&logger, log::Level::Error, "my_app", "kept by the default");
+            log(&logger, log::Level::Debug, "my_app::db", "kept for db");
+            log(&logger, log::Level::Error, "my_app::db::pool", "turned off");
+            log(
+                &logger,
+                log::Level::Error,
+                "hyper::client",
+                "always ignored",
+            );
+
+            transport.assert_count(2);
+            transport.assert_message(Level::Error, "kept by the default");
+            transport.assert_message(Level::Debug, "kept for db");
+        }
+
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl Log for Recorder {
+            fn enabled(&self, _metadata: &Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        #[test]
+        fn test_wrap_forwards_to_inner_logger() {
+            let (logger, transport) = logger();
+            let recorded = Arc::new(Mutex::new(Vec::new()));
+            let logger = logger.wrap(Box::new(Recorder(recorded.clone())));
+
+            log(&logger, log::Level::Error, "my_app", "reported");
+            log(&logger, log::Level::Debug, "my_app", "below the level");
+
+            assert_eq!(
+                *recorded.lock().unwrap(),
+                vec!["reported", "below the level"]
+            );
+            assert!(logger.enabled(&Metadata::builder().level(log::Level::Trace).build()));
+            transport.assert_count(1);
+            transport.assert_message(Level::Error, "reported");
+        }
+    }
+}
@@ -1,54 +1,177 @@
 #[macro_use]
 extern crate log;
 
-use rollbar_rust::constants;
 use rollbar_rust::types::*;
-use rollbar_rust::Uuid;
-use rollbar_rust::{Configuration, HttpTransport, Transport};
+use rollbar_rust::{
+    ConfigLoader, Configuration, Deploy, DeployClient, FileTransport, HttpTransport, Rollbar,
+    Transport,
+};
+use std::env;
+use std::process;
 use std::time::Duration;
 
 fn main() {
     simple_logger::init_with_level(log::Level::Info).unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("replay") => replay(&args[1..]),
+        Some("deploy") => deploy(&args[1..]),
+        _ => send_example(),
+    }
+}
+
+fn send_example() {
     let conf = make_configuration();
-    let transport = HttpTransport::new(conf.clone()).unwrap();
-    let item = make_item(&conf);
-    transport.send(item);
+    let rollbar = Rollbar::new(conf).unwrap();
+    let data = make_data();
+    if let Err(err) = rollbar.send(data) {
+        error!("{}", err);
+    }
+    rollbar.shutdown(Duration::from_secs(5));
+}
+
+/// Upload items collected by a `FileTransport` as they were recorded, without
+/// sampling, fingerprinting or truncating them again. The access token in
+/// each item is used unless `ROLLBAR_ACCESS_TOKEN` is set. The endpoint, proxy
+/// and timeout come from `rollbar.conf` and `ROLLBAR_*` variables, as for
+/// `deploy`.
+fn replay(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("usage: rollbar-rust replay FILE...");
+        process::exit(2);
+    }
+
+    let mut items = Vec::new();
+    for path in paths {
+        let read = FileTransport::read_items(path).unwrap_or_else(|err| {
+            error!("{}: {}", path, err);
+            process::exit(1);
+        });
+        info!("Replaying {} items from {}", read.len(), path);
+        items.extend(read);
+    }
+    if items.is_empty() {
+        return;
+    }
+    if let Ok(access_token) = env::var("ROLLBAR_ACCESS_TOKEN") {
+        for item in &mut items {
+            item.access_token = access_token.clone();
+        }
+    }
+
+    let mut conf = ConfigLoader::new()
+        .file("rollbar.conf")
+        .load()
+        .unwrap_or_else(|err| fail(err))
+        .configuration;
+    if conf.access_token.is_none() {
+        conf.access_token = items.first().map(|item| item.access_token.clone());
+    }
+    // The files are uploaded as fast as the API allows.
+    conf.items_per_minute = 0;
+    let transport = HttpTransport::new(conf).unwrap_or_else(|err| fail(err));
+
+    let mut failed = 0;
+    for item in items {
+        if let Err(err) = futures::executor::block_on(transport.send_processed(item)) {
+            error!("{}", err);
+            failed += 1;
+        }
+    }
     transport.shutdown(Duration::from_secs(5));
+    if failed > 0 {
+        error!("{} items could not be replayed", failed);
+        process::exit(1);
+    }
+}
+
+const DEPLOY_USAGE: &str =
+    "usage: rollbar-rust deploy REVISION [--environment ENV] [--status STATUS]
+                           [--comment TEXT] [--rollbar-username NAME] [--local-username NAME]
+       rollbar-rust deploy update DEPLOY_ID STATUS";
+
+/// Record a deploy and print its id, or update the status of one. The access
+/// token, endpoint, proxy and default environment come from `rollbar.conf`
+/// and `ROLLBAR_*` variables, as for the agents.
+fn deploy(args: &[String]) {
+    let conf = ConfigLoader::new()
+        .file("rollbar.conf")
+        .load()
+        .unwrap_or_else(|err| fail(err))
+        .configuration;
+    let client = DeployClient::new(&conf).unwrap_or_else(|err| fail(err));
+    let runtime = tokio::runtime::Runtime::new().unwrap_or_else(|err| fail(err));
+
+    if args.first().map(String::as_str) == Some("update") {
+        let (deploy_id, status) = match &args[1..] {
+            [deploy_id, status] => (
+                deploy_id.parse().unwrap_or_else(|_| usage(DEPLOY_USAGE)),
+                status.parse().unwrap_or_else(|err| fail(err)),
+            ),
+            _ => usage(DEPLOY_USAGE),
+        };
+        if let Err(err) = runtime.block_on(client.update(deploy_id, status)) {
+            fail(err);
+        }
+        return;
+    }
+
+    let mut deploy = Deploy::builder()
+        .maybe_local_username(env::var("USER").ok())
+        .environment(conf.environment.unwrap_or_default())
+        .build();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().unwrap_or_else(|| usage(DEPLOY_USAGE));
+        match arg.as_str() {
+            "--environment" => deploy.environment = value(),
+            "--status" => deploy.status = Some(value().parse().unwrap_or_else(|err| fail(err))),
+            "--comment" => deploy.comment = Some(value()),
+            "--rollbar-username" => deploy.rollbar_username = Some(value()),
+            "--local-username" => deploy.local_username = Some(value()),
+            flag if flag.starts_with("--") => usage(DEPLOY_USAGE),
+            revision if deploy.revision.is_empty() => deploy.revision = revision.to_owned(),
+            _ => usage(DEPLOY_USAGE),
+        }
+    }
+    if deploy.revision.is_empty() || deploy.environment.is_empty() {
+        usage(DEPLOY_USAGE);
+    }
+
+    match runtime.block_on(client.report(&deploy)) {
+        Ok(deploy_id) => println!("{}", deploy_id),
+        Err(err) => fail(err),
+    }
+}
+
+fn usage(usage: &str) -> ! {
+    eprintln!("{}", usage);
+    process::exit(2);
+}
+
+fn fail<E: std::fmt::Display>(err: E) -> ! {
+    error!("{}", err);
+    process::exit(1);
 }
 
 fn make_configuration() -> Configuration {
-    let mut conf = Configuration::default();
-    conf.access_token = Some("POST_SERVER_ITEM_TOKEN".to_owned());
-    conf
+    Configuration {
+        access_token: Some("POST_SERVER_ITEM_TOKEN".to_owned()),
+        environment: Some("testing".to_owned()),
+        host: Some("localhost".to_owned()),
+        ..Configuration::default()
+    }
 }
 
-fn make_item(configuration: &Configuration) -> Item {
+fn make_data() -> Data {
     let message = Message::builder().body("Hello, Rust").build();
 
     let body = Body::builder().message(message).build();
 
-    let server = Server::builder()
-        .cpu(constants::ARCH)
-        .host("localhost")
-        .build();
-
-    let uuid = Uuid::new();
-    info!("Building: {}", uuid);
-
-    let data = Data::builder()
+    Data::builder()
         .body(body)
-        .environment("testing")
         .level(Level::Error)
-        .platform(constants::PLATFORM)
         .language("rust")
-        .server(server)
         .person(Person::builder().id("42").username("bob").build())
-        .notifier(constants::NOTIFIER.clone())
-        .uuid(uuid)
-        .build();
-
-    Item::builder()
-        .access_token(configuration.access_token.clone().unwrap())
-        .data(data)
         .build()
 }
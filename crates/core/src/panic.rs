@@ -0,0 +1,174 @@
+use std::backtrace::Backtrace;
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::client::Rollbar;
+use crate::types::*;
+
+/// Frames belonging to the panic machinery itself rather than the code that
+/// panicked.
+const SKIPPED_FRAME_PREFIXES: &[&str] = &[
+    "std::backtrace",
+    "std::panicking",
+    "std::panic::",
+    "core::panicking",
+    "rust_begin_unwind",
+    "rollbar_rust::panic",
+    "<alloc::boxed::Box<F,A> as core::ops::function::Fn",
+];
+
+/// Report every panic to Rollbar as a critical item, then wait up to
+/// `timeout` for it to be sent in case the process aborts. The previously
+/// installed hook still runs afterwards.
+///
+/// The transport stays open, so panics caught further up, e.g. in a thread
+/// that is restarted, don't stop later items from being sent.
+pub fn set_hook(rollbar: Arc<Rollbar>, timeout: Duration) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| (*s).to_owned())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_owned());
+        let location = info
+            .location()
+            .map(|l| (l.file().to_owned(), l.line(), l.column()));
+        let backtrace = Backtrace::force_capture().to_string();
+
+        let data = panic_data(message, location, &backtrace);
+        if let Err(err) = rollbar.send(data) {
+            error!("could not report panic: {}", err);
+        }
+        rollbar.flush(timeout);
+
+        previous(info);
+    }));
+}
+
+fn panic_data(message: String, location: Option<(String, u32, u32)>, backtrace: &str) -> Data {
+    let thread = thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+    let description = match &location {
+        Some((file, line, col)) => format!(
+            "thread '{}' panicked at {}:{}:{}",
+            thread_name, file, line, col
+        ),
+        None => format!("thread '{}' panicked", thread_name),
+    };
+
+    let mut frames = frames_from_backtrace(backtrace);
+    if frames.is_empty() {
+        if let Some((file, line, col)) = location {
+            frames.push(
+                Frame::builder()
+                    .filename(file)
+                    .lineno(line as i32)
+                    .colno(col as i32)
+                    .build(),
+            );
+        }
+    }
+
+    let exception = Exception::builder()
+        .class("Panic")
+        .message(message)
+        .description(description)
+        .build();
+    let trace = Trace::builder().frames(frames).exception(exception).build();
+    let body = Body::builder().trace(trace).build();
+
+    Data::builder()
+        .body(body)
+        .level(Level::Critical)
+        .language("rust")
+        .build()
+}
+
+/// Parse the `Display` output of a `std::backtrace::Backtrace` into frames,
+/// oldest call first as the API expects.
+pub(crate) fn frames_from_backtrace(backtrace: &str) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = Vec::new();
+    for line in backtrace.lines() {
+        let line = line.trim();
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                let mut parts = location.rsplitn(3, ':');
+                let colno = parts.next().and_then(|s| s.parse().ok());
+                let lineno = parts.next().and_then(|s| s.parse().ok());
+                match (parts.next(), lineno, colno) {
+                    (Some(filename), Some(_), Some(_)) => {
+                        frame.filename = filename.to_owned();
+                        frame.lineno = lineno;
+                        frame.colno = colno;
+                    }
+                    _ => frame.filename = location.to_owned(),
+                }
+            }
+        } else if let Some((index, method)) = line.split_once(": ") {
+            if index.chars().all(|c| c.is_ascii_digit()) {
+                frames.push(Frame::builder().method(method).build());
+            }
+        }
+    }
+
+    frames.retain(|frame| {
+        let method = frame.method.as_deref().unwrap_or_default();
+        !SKIPPED_FRAME_PREFIXES
+            .iter()
+            .any(|prefix| method.starts_with(prefix))
+    });
+    frames.reverse();
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKTRACE: &str = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/abc/library/std/src/backtrace.rs:312:13
+   1: core::panicking::panic_fmt
+             at /rustc/abc/library/core/src/panicking.rs:75:14
+   2: app::parse
+             at ./src/main.rs:10:5
+   3: app::main
+             at ./src/main.rs:4:5
+   4: __libc_start_main
+   5: _start
+";
+
+    #[test]
+    fn test_frames_from_backtrace() {
+        let frames = frames_from_backtrace(BACKTRACE);
+
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].method.as_deref(), Some("_start"));
+        assert_eq!(frames[0].filename, "");
+        assert_eq!(frames[2].method.as_deref(), Some("app::main"));
+        assert_eq!(frames[3].method.as_deref(), Some("app::parse"));
+        assert_eq!(frames[3].filename, "./src/main.rs");
+        assert_eq!(frames[3].lineno, Some(10));
+        assert_eq!(frames[3].colno, Some(5));
+    }
+
+    #[test]
+    fn test_panic_data_falls_back_to_location() {
+        let data = panic_data("boom".to_owned(), Some(("src/lib.rs".to_owned(), 7, 3)), "");
+
+        assert_eq!(data.level, Some(Level::Critical));
+        match data.body {
+            Body::TraceBody { trace, .. } => {
+                assert_eq!(trace.exception.class, "Panic");
+                assert_eq!(trace.exception.message.as_deref(), Some("boom"));
+                assert_eq!(trace.frames.len(), 1);
+                assert_eq!(trace.frames[0].filename, "src/lib.rs");
+                assert_eq!(trace.frames[0].lineno, Some(7));
+            }
+            _ => panic!("expected a trace body"),
+        }
+    }
+}
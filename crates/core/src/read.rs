@@ -0,0 +1,328 @@
+use reqwest::Method;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::api::Api;
+use crate::configuration::Configuration;
+use crate::errors;
+use crate::transport::SendError;
+use crate::types::{Data, Item, Level};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Active,
+    Resolved,
+    Muted,
+    Archived,
+}
+
+/// An item as the API tracks it: the group occurrences of the same problem
+/// are counted under.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ItemRecord {
+    pub id: u64,
+    /// The item's number within its project, as shown in the web app.
+    pub counter: u64,
+    pub status: ItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_occurrences: Option<u64>,
+    /// In seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_occurrence_timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_occurrence_timestamp: Option<u64>,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A single occurrence of an item, with the data it was reported with.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Occurrence {
+    pub id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<u64>,
+    /// In seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    pub data: Data,
+
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Occurrence {
+    /// The occurrence as an item that can be sent again, e.g. to another
+    /// project.
+    pub fn into_item<S: Into<String>>(self, access_token: S) -> Item {
+        Item::builder()
+            .access_token(access_token)
+            .data(self.data)
+            .build()
+    }
+}
+
+/// Which items `ReadClient::items` lists. Pages are numbered from 1.
+#[derive(Clone, Serialize, Debug, Default, Builder)]
+pub struct ItemQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ItemStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemsPage {
+    items: Vec<ItemRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OccurrencesPage {
+    instances: Vec<Occurrence>,
+}
+
+/// Queries a project's items and occurrences with a token that has the
+/// `read` scope. Resolving and muting items also needs the `write` scope.
+///
+/// Everything but the access token, i.e. the endpoint, timeout and proxy,
+/// comes from the `Configuration`. The futures it returns must be run
+/// within a Tokio runtime.
+#[derive(Debug, Clone)]
+pub struct ReadClient {
+    api: Api,
+}
+
+impl ReadClient {
+    pub fn new<S: Into<String>>(
+        configuration: &Configuration,
+        read_token: S,
+    ) -> errors::Result<Self> {
+        let configuration = Configuration {
+            access_token: Some(read_token.into()),
+            ..configuration.clone()
+        };
+        Ok(ReadClient {
+            api: Api::new(&configuration)?,
+        })
+    }
+
+    /// One page of the items matching `query`, most recent first.
+    pub async fn items(&self, query: &ItemQuery) -> Result<Vec<ItemRecord>, SendError> {
+        let request = self.api.request(Method::GET, "items/")?.query(query);
+        let page: ItemsPage = Api::result(request).await?;
+        Ok(page.items)
+    }
+
+    pub async fn item(&self, item_id: u64) -> Result<ItemRecord, SendError> {
+        let request = self
+            .api
+            .request(Method::GET, &format!("item/{}", item_id))?;
+        Api::result(request).await
+    }
+
+    /// The item with the counter shown for it in the web app.
+    pub async fn item_by_counter(&self, counter: u64) -> Result<ItemRecord, SendError> {
+        let path = format!("item_by_counter/{}", counter);
+        Api::result(self.api.request(Method::GET, &path)?).await
+    }
+
+    /// One page of the occurrences of an item, most recent first. Pages are
+    /// numbered from 1.
+    pub async fn occurrences(&self, item_id: u64, page: u32) -> Result<Vec<Occurrence>, SendError> {
+        let request = self
+            .api
+            .request(Method::GET, &format!("item/{}/instances/", item_id))?
+            .query(&[("page", page)]);
+        let page: OccurrencesPage = Api::result(request).await?;
+        Ok(page.instances)
+    }
+
+    /// The occurrence with `uuid` as its `data.uuid`, or with `uuid` as its
+    /// id.
+    pub async fn occurrence(&self, uuid: &str) -> Result<Occurrence, SendError> {
+        let request = self
+            .api
+            .request(Method::GET, &format!("instance/{}", uuid))?;
+        Api::result(request).await
+    }
+
+    pub async fn resolve(&self, item_id: u64) -> Result<ItemRecord, SendError> {
+        self.set_status(item_id, ItemStatus::Resolved).await
+    }
+
+    pub async fn mute(&self, item_id: u64) -> Result<ItemRecord, SendError> {
+        self.set_status(item_id, ItemStatus::Muted).await
+    }
+
+    pub async fn set_status(
+        &self,
+        item_id: u64,
+        status: ItemStatus,
+    ) -> Result<ItemRecord, SendError> {
+        let request = self
+            .api
+            .request(Method::PATCH, &format!("item/{}", item_id))?
+            .json(&serde_json::json!({ "status": status }));
+        Api::result(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::stand_in::{self, Reply, StandIn};
+    use crate::types::{Body, Message};
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+
+    fn client(server: &StandIn) -> ReadClient {
+        ReadClient::new(
+            &Configuration {
+                endpoint: server.url.clone(),
+                ..Configuration::default()
+            },
+            "read-token",
+        )
+        .unwrap()
+    }
+
+    fn result(result: Value) -> Reply {
+        (
+            200,
+            vec![],
+            json!({ "err": 0, "result": result }).to_string(),
+        )
+    }
+
+    fn item_json(status: &str) -> Value {
+        json!({
+            "id": 272505123,
+            "counter": 17,
+            "status": status,
+            "environment": "production",
+            "title": "TypeError: x is undefined",
+            "total_occurrences": 3,
+            "level": "error",
+        })
+    }
+
+    #[test]
+    fn test_items() {
+        let server = StandIn::start(vec![
+            result(json!({ "items": [item_json("active")], "page": 2 })),
+            result(item_json("active")),
+            (
+                301,
+                vec![("Location", "/api/1/item/272505123".to_owned())],
+                String::new(),
+            ),
+            result(item_json("active")),
+        ]);
+        let client = client(&server);
+        let runtime = Runtime::new().unwrap();
+
+        let query = ItemQuery::builder()
+            .status(ItemStatus::Active)
+            .level(Level::Error)
+            .page(2u32)
+            .build();
+        let items = runtime.block_on(client.items(&query)).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].counter, 17);
+        assert_eq!(items[0].status, ItemStatus::Active);
+        assert_eq!(items[0].extra["level"], "error");
+
+        let item = runtime.block_on(client.item(272505123)).unwrap();
+        assert_eq!(item.title.as_deref(), Some("TypeError: x is undefined"));
+        let item = runtime.block_on(client.item_by_counter(17)).unwrap();
+        assert_eq!(item.id, 272505123);
+
+        assert_eq!(
+            server.request_lines(),
+            vec![
+                "GET /api/1/items/?status=active&level=error&page=2",
+                "GET /api/1/item/272505123",
+                "GET /api/1/item_by_counter/17",
+                "GET /api/1/item/272505123",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_occurrences_deserialize_into_data() {
+        let data = Data::builder()
+            .body(
+                Body::builder()
+                    .message(Message::builder().body("hello").build())
+                    .build(),
+            )
+            .level(Level::Warning)
+            .uuid("d4c7acef-55bf-4504-9ebd-4ef8f0b1f4ab")
+            .build();
+        let occurrence = json!({
+            "id": 94320,
+            "item_id": 272505123,
+            "timestamp": 1660000000,
+            "version": 2,
+            "data": data,
+        });
+        let server = StandIn::start(vec![
+            result(json!({ "instances": [occurrence], "page": 1 })),
+            result(occurrence),
+        ]);
+        let client = client(&server);
+        let runtime = Runtime::new().unwrap();
+
+        let occurrences = runtime.block_on(client.occurrences(272505123, 1)).unwrap();
+        assert_eq!(occurrences.len(), 1);
+        assert_eq!(occurrences[0].extra["version"], 2);
+        let occurrence = runtime
+            .block_on(client.occurrence("d4c7acef-55bf-4504-9ebd-4ef8f0b1f4ab"))
+            .unwrap();
+
+        let item = occurrence.into_item("post-token");
+        assert_eq!(item.access_token, "post-token");
+        assert_eq!(item.data.level, Some(Level::Warning));
+        assert_eq!(item.data.body.message(), Some("hello"));
+        assert_eq!(
+            server.request_lines(),
+            vec![
+                "GET /api/1/item/272505123/instances/?page=1",
+                "GET /api/1/instance/d4c7acef-55bf-4504-9ebd-4ef8f0b1f4ab",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_and_mute() {
+        let server = StandIn::start(vec![
+            result(item_json("resolved")),
+            result(item_json("muted")),
+            stand_in::error(403, "access token does not have write scope"),
+        ]);
+        let client = client(&server);
+        let runtime = Runtime::new().unwrap();
+
+        let item = runtime.block_on(client.resolve(272505123)).unwrap();
+        assert_eq!(item.status, ItemStatus::Resolved);
+        let item = runtime.block_on(client.mute(272505123)).unwrap();
+        assert_eq!(item.status, ItemStatus::Muted);
+        let err = runtime.block_on(client.resolve(272505123)).unwrap_err();
+        assert!(matches!(err, SendError::Api { status: 403, .. }));
+
+        assert_eq!(
+            server.requests()[..2],
+            [r#"{"status":"resolved"}"#, r#"{"status":"muted"}"#]
+        );
+        assert_eq!(server.request_lines()[0], "PATCH /api/1/item/272505123");
+    }
+}
@@ -0,0 +1,171 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::configuration::Configuration;
+use crate::constants;
+use crate::types::*;
+
+/// The key under `notifier.diagnostic` the sample rate of a kept item is recorded at.
+pub const SAMPLE_RATE_KEY: &str = "sample_rate";
+
+/// Randomly drops items according to the configured sample rates.
+///
+/// A rate in `fingerprint_sample_rates` applies to items whose fingerprint
+/// or outermost exception class matches its key. Other items use the rate
+/// for their level from `sample_rates`. Items without a rate are always
+/// kept. Kept items that were subject to a rate below one have the rate
+/// recorded in `notifier.diagnostic`, so counts can be extrapolated.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    levels: HashMap<Level, f64>,
+    fingerprints: HashMap<String, f64>,
+}
+
+impl Sampler {
+    pub fn new(configuration: &Configuration) -> Self {
+        let clamp = |rate: &f64| rate.clamp(0.0, 1.0);
+        Sampler {
+            levels: configuration
+                .sample_rates
+                .iter()
+                .map(|(level, rate)| (level.clone(), clamp(rate)))
+                .collect(),
+            fingerprints: configuration
+                .fingerprint_sample_rates
+                .iter()
+                .map(|(key, rate)| (key.clone(), clamp(rate)))
+                .collect(),
+        }
+    }
+
+    /// The rate `item` is sampled at, if any.
+    pub fn rate(&self, item: &Item) -> Option<f64> {
+        let data = &item.data;
+        data.fingerprint
+            .iter()
+            .map(String::as_str)
+            .chain(data.body.exception_classes().first().copied())
+            .find_map(|key| self.fingerprints.get(key))
+            .or_else(|| self.levels.get(&data.level.clone().unwrap_or_default()))
+            .copied()
+    }
+
+    /// Whether to keep `item`.
+    pub fn sample_item(&self, item: &mut Item) -> bool {
+        self.sample_with(item, random())
+    }
+
+    fn sample_with(&self, item: &mut Item, random: f64) -> bool {
+        let rate = match self.rate(item) {
+            Some(rate) if rate < 1.0 => rate,
+            _ => return true,
+        };
+        if random >= rate {
+            return false;
+        }
+        item.data
+            .notifier
+            .get_or_insert_with(|| constants::NOTIFIER.clone())
+            .diagnostic
+            .get_or_insert_with(HashMap::new)
+            .insert(SAMPLE_RATE_KEY.to_owned(), Value::from(rate));
+        true
+    }
+}
+
+/// A uniformly distributed number in `[0, 1)`.
+#[cfg(not(target_arch = "wasm32"))]
+fn random() -> f64 {
+    fastrand::f64()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn random() -> f64 {
+    js_sys::Math::random()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler() -> Sampler {
+        Sampler::new(&Configuration {
+            sample_rates: [(Level::Warning, 0.1), (Level::Debug, 0.0)]
+                .into_iter()
+                .collect(),
+            fingerprint_sample_rates: [
+                ("TimeoutError".to_owned(), 0.5),
+                ("noisy".to_owned(), 0.25),
+            ]
+            .into_iter()
+            .collect(),
+            ..Configuration::default()
+        })
+    }
+
+    fn item(level: Level) -> Item {
+        let message = Message::builder().body("hello").build();
+        let data = Data::builder()
+            .body(Body::builder().message(message).build())
+            .level(level)
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    fn sample_rate(item: &Item) -> Option<&Value> {
+        item.data
+            .notifier
+            .as_ref()?
+            .diagnostic
+            .as_ref()?
+            .get(SAMPLE_RATE_KEY)
+    }
+
+    #[test]
+    fn test_samples_by_level() {
+        let sampler = sampler();
+
+        let mut critical = item(Level::Critical);
+        assert!(sampler.sample_with(&mut critical, 0.99));
+        assert_eq!(sample_rate(&critical), None);
+
+        let mut warning = item(Level::Warning);
+        warning.data.custom = Some(HashMap::from([(
+            SAMPLE_RATE_KEY.to_owned(),
+            Value::from("mine"),
+        )]));
+        assert!(!sampler.sample_with(&mut warning, 0.1));
+        assert!(sampler.sample_with(&mut warning, 0.05));
+        assert_eq!(sample_rate(&warning), Some(&Value::from(0.1)));
+        assert_eq!(warning.data.custom.unwrap()[SAMPLE_RATE_KEY], "mine");
+
+        assert!(!sampler.sample_with(&mut item(Level::Debug), 0.0));
+    }
+
+    #[test]
+    fn test_fingerprint_and_class_rates_take_precedence() {
+        let sampler = sampler();
+
+        let mut fingerprinted = item(Level::Warning);
+        fingerprinted.data.fingerprint = Some("noisy".to_owned());
+        assert_eq!(sampler.rate(&fingerprinted), Some(0.25));
+
+        let trace = Trace::builder()
+            .exception(Exception::builder().class("TimeoutError").build())
+            .build();
+        let mut timeout = item(Level::Critical);
+        timeout.data.body = Body::builder().trace(trace).build();
+        assert_eq!(sampler.rate(&timeout), Some(0.5));
+    }
+
+    #[test]
+    fn test_rates_from_json() {
+        let configuration: Configuration =
+            serde_json::from_str(r#"{"sampleRates": {"warning": 0.1, "info": 2}}"#).unwrap();
+        let sampler = Sampler::new(&configuration);
+
+        assert_eq!(sampler.rate(&item(Level::Warning)), Some(0.1));
+        assert_eq!(sampler.rate(&item(Level::Info)), Some(1.0));
+        assert_eq!(sampler.rate(&item(Level::Error)), None);
+    }
+}
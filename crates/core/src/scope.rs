@@ -0,0 +1,255 @@
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::future::Future;
+
+use crate::types::{Data, Person};
+
+/// The key under `data.custom` scope tags are reported at.
+pub const TAGS_KEY: &str = "tags";
+
+thread_local! {
+    static STACK: RefCell<Vec<Arc<Scope>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+tokio::task_local! {
+    static TASK_STACK: RefCell<Vec<Arc<Scope>>>;
+}
+
+/// Person, context, tags and custom data merged into every item reported
+/// while the scope is active.
+///
+/// Scopes nest: values from an inner scope override those from the scopes
+/// around it, and values set on the item itself override them all. Scopes
+/// pushed on a thread apply to that thread. In async code use `run_async`,
+/// which keeps the scope with the task wherever it runs, and within which
+/// `push` applies to the task.
+///
+/// ```
+/// use rollbar_rust::Scope;
+///
+/// let _scope = Scope::new().context("checkout").tag("region", "eu").push();
+/// // Items reported here have their context and region tag set.
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub person: Option<Person>,
+    pub context: Option<String>,
+    pub tags: HashMap<String, String>,
+    pub custom: HashMap<String, Value>,
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Scope::default()
+    }
+
+    pub fn person(mut self, person: Person) -> Self {
+        self.person = Some(person);
+        self
+    }
+
+    pub fn context<S: Into<String>>(mut self, context: S) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn tag<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn custom<K: Into<String>, V: Into<Value>>(mut self, key: K, value: V) -> Self {
+        self.custom.insert(key.into(), value.into());
+        self
+    }
+
+    /// Everything in the active scopes, merged.
+    pub fn current() -> Scope {
+        with_stack(|stack| stack.last().map(|scope| Scope::clone(scope))).unwrap_or_default()
+    }
+
+    /// Make this scope active until the guard is dropped.
+    pub fn push(self) -> ScopeGuard {
+        with_stack(|stack| {
+            let merged = match stack.last() {
+                Some(outer) => outer.merged(self),
+                None => self,
+            };
+            stack.push(Arc::new(merged));
+            ScopeGuard {
+                depth: stack.len(),
+                _not_send: PhantomData,
+            }
+        })
+    }
+
+    /// Run `f` with this scope active.
+    pub fn run<R, F: FnOnce() -> R>(self, f: F) -> R {
+        let _guard = self.push();
+        f()
+    }
+
+    /// Run `future` with this scope, on top of the scopes active now, active
+    /// for as long as the future runs, on whichever thread it is polled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_async<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+        let merged = Scope::current().merged(self);
+        TASK_STACK.scope(RefCell::new(vec![Arc::new(merged)]), future)
+    }
+
+    /// Fill in whatever `data` does not already have from this scope.
+    pub fn apply(&self, data: &mut Data) {
+        if data.person.is_none() {
+            data.person = self.person.clone();
+        }
+        if data.context.is_none() {
+            data.context = self.context.clone();
+        }
+        if self.custom.is_empty() && self.tags.is_empty() {
+            return;
+        }
+        let custom = data.custom.get_or_insert_with(HashMap::new);
+        for (key, value) in &self.custom {
+            custom.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        if !self.tags.is_empty() {
+            let tags = custom
+                .entry(TAGS_KEY.to_owned())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(tags) = tags {
+                for (key, value) in &self.tags {
+                    tags.entry(key.clone())
+                        .or_insert_with(|| Value::from(value.as_str()));
+                }
+            }
+        }
+    }
+
+    fn merged(&self, inner: Scope) -> Scope {
+        let mut merged = self.clone();
+        if inner.person.is_some() {
+            merged.person = inner.person;
+        }
+        if inner.context.is_some() {
+            merged.context = inner.context;
+        }
+        merged.tags.extend(inner.tags);
+        merged.custom.extend(inner.custom);
+        merged
+    }
+}
+
+/// Deactivates a pushed scope, and any pushed after it, when dropped.
+///
+/// The guard is not `Send`: it has to be dropped on the thread, or within the
+/// `Scope::run_async` task, whose stack the scope was pushed on. Across an
+/// `.await`, use `Scope::run_async` instead.
+#[derive(Debug)]
+#[must_use = "the scope is popped as soon as the guard is dropped"]
+pub struct ScopeGuard {
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let depth = self.depth;
+        with_stack(|stack| stack.truncate(depth - 1));
+    }
+}
+
+/// Run `f` on the current task's scope stack, or the thread's outside of
+/// `Scope::run_async`.
+fn with_stack<R, F: FnOnce(&mut Vec<Arc<Scope>>) -> R>(f: F) -> R {
+    let mut f = Some(f);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let result = TASK_STACK.try_with(|stack| (f.take().unwrap())(&mut stack.borrow_mut()));
+        if let Ok(result) = result {
+            return result;
+        }
+    }
+    STACK.with(|stack| (f.take().unwrap())(&mut stack.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn tags(data: &Data) -> &Value {
+        &data.custom.as_ref().unwrap()[TAGS_KEY]
+    }
+
+    #[test]
+    fn test_nested_scopes_override_outer_ones() {
+        let _outer = Scope::new()
+            .context("outer")
+            .tag("region", "eu")
+            .tag("tier", "free")
+            .custom("request_id", 1)
+            .push();
+        {
+            let _inner = Scope::new()
+                .person(Person::builder().id("42").build())
+                .context("inner")
+                .tag("tier", "paid")
+                .push();
+
+            let mut data = Data::builder()
+                .custom(HashMap::from([("request_id".to_owned(), Value::from(2))]))
+                .build();
+            Scope::current().apply(&mut data);
+
+            assert_eq!(data.context.as_deref(), Some("inner"));
+            assert_eq!(data.person.as_ref().unwrap().id, "42");
+            assert_eq!(tags(&data)["region"], "eu");
+            assert_eq!(tags(&data)["tier"], "paid");
+            assert_eq!(data.custom.as_ref().unwrap()["request_id"], 2);
+        }
+
+        let current = Scope::current();
+        assert_eq!(current.context.as_deref(), Some("outer"));
+        assert!(current.person.is_none());
+        assert_eq!(current.tags["tier"], "free");
+    }
+
+    #[test]
+    fn test_scopes_are_per_thread() {
+        Scope::new().context("main").run(|| {
+            let other = thread::spawn(|| Scope::current().context).join().unwrap();
+            assert_eq!(other, None);
+            assert_eq!(Scope::current().context.as_deref(), Some("main"));
+        });
+        assert_eq!(Scope::current().context, None);
+    }
+
+    #[test]
+    fn test_scopes_follow_tasks() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _outer = Scope::new().tag("region", "eu").push();
+
+        let task = Scope::new().context("task").run_async(async {
+            tokio::task::yield_now().await;
+            Scope::new()
+                .tag("step", "charge")
+                .run_async(async {
+                    tokio::task::yield_now().await;
+                    Scope::current()
+                })
+                .await
+        });
+        let current = runtime.block_on(runtime.spawn(task)).unwrap();
+
+        assert_eq!(current.context.as_deref(), Some("task"));
+        assert_eq!(current.tags["region"], "eu");
+        assert_eq!(current.tags["step"], "charge");
+        assert_eq!(Scope::current().context, None);
+    }
+}
@@ -0,0 +1,377 @@
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::configuration::{CaptureIp, Configuration};
+use crate::types::*;
+
+/// Replaces sensitive values in an `Item` before it leaves the host.
+///
+/// Any map key, query string parameter or header whose name is in
+/// `scrub_fields` (case-insensitively, unless whitelisted) has its value
+/// replaced with the mask. Every string value is also matched against the
+/// `scrub_patterns` regexes and matching text, including in the title, is
+/// masked. `request.user_ip` is kept, anonymized or removed according to
+/// `capture_ip`.
+#[derive(Debug, Clone)]
+pub struct Scrubber {
+    fields: HashSet<String>,
+    whitelist: HashSet<String>,
+    patterns: Vec<Regex>,
+    mask: String,
+    capture_ip: CaptureIp,
+}
+
+impl Scrubber {
+    pub fn new(configuration: &Configuration) -> Self {
+        let lowercase = |fields: &[String]| fields.iter().map(|f| f.to_lowercase()).collect();
+        let patterns = configuration
+            .scrub_patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    warn!("ignoring invalid scrub pattern {}: {}", pattern, err);
+                    None
+                }
+            })
+            .collect();
+
+        Scrubber {
+            fields: lowercase(&configuration.scrub_fields),
+            whitelist: lowercase(&configuration.scrub_whitelist),
+            patterns,
+            mask: configuration.scrub_mask.clone(),
+            capture_ip: configuration.capture_ip,
+        }
+    }
+
+    pub fn scrub_item(&self, item: &mut Item) {
+        let data = &mut item.data;
+        self.scrub_body(&mut data.body);
+        if let Some(title) = &mut data.title {
+            self.scrub_str(title);
+        }
+        self.scrub_option_map(&mut data.custom);
+        if let Some(person) = &mut data.person {
+            if let Some(username) = &mut person.username {
+                self.scrub_field("username", username);
+            }
+            if let Some(email) = &mut person.email {
+                self.scrub_field("email", email);
+            }
+        }
+        if let Some(request) = &mut data.request {
+            self.scrub_request(request);
+        }
+        if let Some(server) = &mut data.server {
+            self.scrub_map(&mut server.extra);
+        }
+        if let Some(client) = &mut data.client {
+            self.scrub_map(&mut client.extra);
+        }
+    }
+
+    fn scrub_body(&self, body: &mut Body) {
+        match body {
+            Body::Unset => {}
+            Body::TraceBody { telemetry, trace } => {
+                self.scrub_telemetry(telemetry);
+                self.scrub_trace(trace);
+            }
+            Body::TraceChainBody {
+                telemetry,
+                trace_chain,
+            } => {
+                self.scrub_telemetry(telemetry);
+                trace_chain.iter_mut().for_each(|t| self.scrub_trace(t));
+            }
+            Body::MessageBody { telemetry, message } => {
+                self.scrub_telemetry(telemetry);
+                self.scrub_str(&mut message.body);
+                self.scrub_map(&mut message.extra);
+            }
+            Body::CrashReportBody {
+                telemetry,
+                crash_report,
+            } => {
+                self.scrub_telemetry(telemetry);
+                self.scrub_str(&mut crash_report.raw);
+            }
+        }
+    }
+
+    fn scrub_telemetry(&self, telemetry: &mut Option<Vec<Telemetry>>) {
+        for event in telemetry.iter_mut().flatten() {
+            self.scrub_map(&mut event.body);
+        }
+    }
+
+    fn scrub_trace(&self, trace: &mut Trace) {
+        if let Some(message) = &mut trace.exception.message {
+            self.scrub_str(message);
+        }
+        if let Some(description) = &mut trace.exception.description {
+            self.scrub_str(description);
+        }
+        for frame in &mut trace.frames {
+            self.scrub_option_map(&mut frame.locals);
+            self.scrub_option_map(&mut frame.context);
+        }
+    }
+
+    fn scrub_request(&self, request: &mut Request) {
+        if let Some(url) = &mut request.url {
+            *url = self.scrub_url(url);
+        }
+        if let Some(query_string) = &mut request.query_string {
+            *query_string = self.scrub_query(query_string);
+        }
+        if let Some(body) = &mut request.body {
+            self.scrub_str(body);
+        }
+        self.scrub_option_map(&mut request.headers);
+        self.scrub_option_map(&mut request.params);
+        self.scrub_option_map(&mut request.get);
+        self.scrub_option_map(&mut request.post);
+        self.scrub_map(&mut request.extra);
+        request.user_ip = match (self.capture_ip, request.user_ip.take()) {
+            (CaptureIp::Full, user_ip) => user_ip,
+            (CaptureIp::Anonymize, Some(user_ip)) => anonymize_ip(&user_ip),
+            _ => None,
+        };
+    }
+
+    fn scrub_option_map(&self, map: &mut Option<HashMap<String, Value>>) {
+        if let Some(map) = map {
+            self.scrub_map(map);
+        }
+    }
+
+    fn scrub_map(&self, map: &mut HashMap<String, Value>) {
+        for (key, value) in map.iter_mut() {
+            self.scrub_value(Some(key), value);
+        }
+    }
+
+    fn scrub_value(&self, key: Option<&str>, value: &mut Value) {
+        if key.is_some_and(|k| self.is_sensitive(k)) {
+            *value = Value::from(self.mask.as_str());
+            return;
+        }
+        match value {
+            Value::String(s) => self.scrub_str(s),
+            Value::Array(values) => values.iter_mut().for_each(|v| self.scrub_value(None, v)),
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    self.scrub_value(Some(key), value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn scrub_field(&self, key: &str, value: &mut String) {
+        if self.is_sensitive(key) {
+            *value = self.mask.clone();
+        } else {
+            self.scrub_str(value);
+        }
+    }
+
+    fn scrub_str(&self, value: &mut String) {
+        for pattern in &self.patterns {
+            if pattern.is_match(value) {
+                *value = pattern.replace_all(value, self.mask.as_str()).into_owned();
+            }
+        }
+    }
+
+    fn scrub_url(&self, url: &str) -> String {
+        match url.split_once('?') {
+            Some((base, rest)) => {
+                let (query, fragment) = match rest.split_once('#') {
+                    Some((query, fragment)) => (query, Some(fragment)),
+                    None => (rest, None),
+                };
+                let mut scrubbed = format!("{}?{}", base, self.scrub_query(query));
+                if let Some(fragment) = fragment {
+                    scrubbed.push('#');
+                    scrubbed.push_str(fragment);
+                }
+                scrubbed
+            }
+            None => url.to_owned(),
+        }
+    }
+
+    fn scrub_query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _)) if self.is_sensitive(key) => format!("{}={}", key, self.mask),
+                Some((key, value)) => {
+                    let mut value = value.to_owned();
+                    self.scrub_str(&mut value);
+                    format!("{}={}", key, value)
+                }
+                None => pair.to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    fn is_sensitive(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.fields.contains(&key) && !self.whitelist.contains(&key)
+    }
+}
+
+/// The network part of `ip`, or nothing if it is not an IP address.
+fn anonymize_ip(ip: &str) -> Option<String> {
+    Some(match ip.trim().parse().ok()? {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            Ipv4Addr::new(a, b, c, 0).to_string()
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, ..] = ip.segments();
+            Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0).to_string()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scrubber() -> Scrubber {
+        Scrubber::new(&Configuration {
+            scrub_patterns: vec![r"\b\d{4}-\d{4}-\d{4}-\d{4}\b".to_owned()],
+            scrub_whitelist: vec!["email".to_owned()],
+            ..Configuration::default()
+        })
+    }
+
+    #[test]
+    fn test_scrub_request() {
+        let mut headers = HashMap::new();
+        headers.insert("Authorization".to_owned(), json!("Bearer abc"));
+        headers.insert("Accept".to_owned(), json!("*/*"));
+        let mut post = HashMap::new();
+        post.insert(
+            "user".to_owned(),
+            json!({"name": "bob", "password": "hunter2", "cards": ["1234-5678-9012-3456"]}),
+        );
+        let request = Request::builder()
+            .url("https://example.com/login?next=/home&access_token=abc#top")
+            .headers(headers)
+            .post(post)
+            .build();
+        let mut item = Item::builder()
+            .data(Data::builder().request(request).build())
+            .build();
+
+        scrubber().scrub_item(&mut item);
+
+        let request = item.data.request.unwrap();
+        assert_eq!(
+            request.url.as_deref(),
+            Some("https://example.com/login?next=/home&access_token=********#top")
+        );
+        let headers = request.headers.unwrap();
+        assert_eq!(headers["Authorization"], "********");
+        assert_eq!(headers["Accept"], "*/*");
+        assert_eq!(
+            request.post.unwrap()["user"],
+            json!({"name": "bob", "password": "********", "cards": ["********"]})
+        );
+    }
+
+    #[test]
+    fn test_scrub_whitelist_and_locals() {
+        let mut locals = HashMap::new();
+        locals.insert("secret".to_owned(), json!(42));
+        let frame = Frame::builder().filename("main.rs").locals(locals).build();
+        let trace = Trace::builder().frames(vec![frame]).build();
+        let data = Data::builder()
+            .body(Body::builder().trace(trace).build())
+            .person(Person::builder().id("1").email("bob@example.com").build())
+            .build();
+        let mut item = Item::builder().data(data).build();
+
+        scrubber().scrub_item(&mut item);
+
+        assert_eq!(
+            item.data.person.unwrap().email.as_deref(),
+            Some("bob@example.com")
+        );
+        match item.data.body {
+            Body::TraceBody { trace, .. } => {
+                assert_eq!(
+                    trace.frames[0].locals.as_ref().unwrap()["secret"],
+                    "********"
+                );
+            }
+            _ => panic!("expected a trace body"),
+        }
+    }
+
+    #[test]
+    fn test_capture_ip() {
+        let user_ip = |capture_ip, user_ip: &str| {
+            let request = Request::builder().user_ip(user_ip).build();
+            let mut item = Item::builder()
+                .data(Data::builder().request(request).build())
+                .build();
+            Scrubber::new(&Configuration {
+                capture_ip,
+                ..Configuration::default()
+            })
+            .scrub_item(&mut item);
+            item.data.request.unwrap().user_ip
+        };
+
+        assert_eq!(
+            user_ip(CaptureIp::Full, "203.0.113.42").as_deref(),
+            Some("203.0.113.42")
+        );
+        assert_eq!(
+            user_ip(CaptureIp::Anonymize, "203.0.113.42").as_deref(),
+            Some("203.0.113.0")
+        );
+        assert_eq!(
+            user_ip(CaptureIp::Anonymize, "2001:db8:85a3::8a2e:370:7334").as_deref(),
+            Some("2001:db8:85a3::")
+        );
+        assert_eq!(user_ip(CaptureIp::Anonymize, "unknown"), None);
+        assert_eq!(user_ip(CaptureIp::None, "203.0.113.42"), None);
+    }
+
+    #[test]
+    fn test_scrub_title_built_from_message() {
+        let pipeline = crate::transport::Pipeline::new(&Configuration {
+            scrub_patterns: vec![r"tok_[a-z0-9]+".to_owned()],
+            grouping_rules: vec![crate::configuration::GroupingRule::default()],
+            ..Configuration::default()
+        });
+        let exception = Exception::builder()
+            .class("AuthError")
+            .message("invalid token tok_4f9a2c")
+            .build();
+        let trace = Trace::builder().exception(exception).build();
+        let data = Data::builder()
+            .body(Body::builder().trace(trace).build())
+            .build();
+        let mut item = Item::builder().data(data).build();
+
+        assert!(pipeline.process(&mut item));
+
+        assert_eq!(
+            item.data.title.as_deref(),
+            Some("AuthError: invalid token ********")
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::types::*;
+
+/// Telemetry recorded in a browser comes from the client, anywhere else from
+/// the server.
+#[cfg(target_arch = "wasm32")]
+const SOURCE: &str = "client";
+#[cfg(not(target_arch = "wasm32"))]
+const SOURCE: &str = "server";
+
+/// A bounded, thread-safe ring buffer of telemetry breadcrumbs.
+///
+/// Once `capacity` events are held the oldest one is evicted. A per-type
+/// limit evicts the oldest event of the same type first, so that e.g. a burst
+/// of log lines does not push out every network event.
+#[derive(Debug)]
+pub struct TelemetryBuffer {
+    events: Mutex<VecDeque<Telemetry>>,
+    capacity: usize,
+    type_limits: HashMap<String, usize>,
+}
+
+impl TelemetryBuffer {
+    pub fn new(capacity: usize) -> Self {
+        TelemetryBuffer {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            type_limits: HashMap::new(),
+        }
+    }
+
+    pub fn type_limit<S: Into<String>>(mut self, telemetry_type: S, limit: usize) -> Self {
+        self.type_limits.insert(telemetry_type.into(), limit);
+        self
+    }
+
+    pub fn push(&self, telemetry: Telemetry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events.lock().unwrap();
+        if let Some(&limit) = self.type_limits.get(&telemetry.telemetry_type) {
+            if limit == 0 {
+                return;
+            }
+            let same_type = |e: &Telemetry| e.telemetry_type == telemetry.telemetry_type;
+            while events.iter().filter(|e| same_type(e)).count() >= limit {
+                if let Some(index) = events.iter().position(same_type) {
+                    events.remove(index);
+                }
+            }
+        }
+        events.push_back(telemetry);
+        while events.len() > self.capacity {
+            events.pop_front();
+        }
+    }
+
+    /// A copy of the buffered events, oldest first.
+    pub fn snapshot(&self) -> Vec<Telemetry> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Telemetry {
+    pub fn log<S: Into<String>>(level: Level, message: S) -> Telemetry {
+        let mut body = HashMap::new();
+        body.insert("message".to_owned(), Value::from(message.into()));
+        Telemetry::new(level, "log", body)
+    }
+
+    pub fn network<M: Into<String>, U: Into<String>>(
+        method: M,
+        url: U,
+        status_code: Option<u16>,
+    ) -> Telemetry {
+        let level = match status_code {
+            Some(status) if status >= 500 => Level::Error,
+            Some(status) if status >= 400 => Level::Warning,
+            _ => Level::Info,
+        };
+        let mut body = HashMap::new();
+        body.insert("method".to_owned(), Value::from(method.into()));
+        body.insert("url".to_owned(), Value::from(url.into()));
+        if let Some(status) = status_code {
+            body.insert("status_code".to_owned(), Value::from(status));
+        }
+        Telemetry::new(level, "network", body)
+    }
+
+    pub fn navigation<F: Into<String>, T: Into<String>>(from: F, to: T) -> Telemetry {
+        let mut body = HashMap::new();
+        body.insert("from".to_owned(), Value::from(from.into()));
+        body.insert("to".to_owned(), Value::from(to.into()));
+        Telemetry::new(Level::Info, "navigation", body)
+    }
+
+    pub fn error<S: Into<String>>(message: S) -> Telemetry {
+        let mut body = HashMap::new();
+        body.insert("message".to_owned(), Value::from(message.into()));
+        Telemetry::new(Level::Error, "error", body)
+    }
+
+    pub fn manual(level: Level, body: HashMap<String, Value>) -> Telemetry {
+        Telemetry::new(level, "manual", body)
+    }
+
+    fn new(level: Level, telemetry_type: &str, body: HashMap<String, Value>) -> Telemetry {
+        Telemetry::builder()
+            .level(level)
+            .telemetry_type(telemetry_type)
+            .source(SOURCE)
+            .timestamp_ms(timestamp_ms())
+            .body(body)
+            .build()
+    }
+}
+
+impl Body {
+    pub fn telemetry_mut(&mut self) -> Option<&mut Option<Vec<Telemetry>>> {
+        match self {
+            Body::Unset => None,
+            Body::TraceBody { telemetry, .. }
+            | Body::MessageBody { telemetry, .. }
+            | Body::TraceChainBody { telemetry, .. }
+            | Body::CrashReportBody { telemetry, .. } => Some(telemetry),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timestamp_ms() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_evicts_oldest() {
+        let buffer = TelemetryBuffer::new(2);
+        buffer.push(Telemetry::log(Level::Info, "one"));
+        buffer.push(Telemetry::log(Level::Info, "two"));
+        buffer.push(Telemetry::log(Level::Info, "three"));
+
+        let events = buffer.snapshot();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].body["message"], "two");
+        assert_eq!(events[1].body["message"], "three");
+    }
+
+    #[test]
+    fn test_buffer_type_limit() {
+        let buffer = TelemetryBuffer::new(10).type_limit("log", 1);
+        buffer.push(Telemetry::network("GET", "/health", Some(200)));
+        buffer.push(Telemetry::log(Level::Info, "one"));
+        buffer.push(Telemetry::log(Level::Info, "two"));
+
+        let events = buffer.snapshot();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].telemetry_type, "network");
+        assert_eq!(events[1].body["message"], "two");
+    }
+
+    #[test]
+    fn test_network_level_from_status() {
+        assert_eq!(
+            Telemetry::network("GET", "/", Some(503)).level,
+            Level::Error
+        );
+        assert_eq!(
+            Telemetry::network("GET", "/", Some(404)).level,
+            Level::Warning
+        );
+        assert_eq!(Telemetry::network("GET", "/", None).level, Level::Info);
+    }
+}
@@ -0,0 +1,235 @@
+use std::any;
+use std::error::Error as StdError;
+
+use crate::types::*;
+
+impl Trace {
+    /// A frameless trace for `error` whose class is the concrete type name.
+    pub fn from_error<E: StdError>(error: &E) -> Trace {
+        Trace::with_class(any::type_name::<E>(), error)
+    }
+
+    /// One trace per error in the `source()` chain, outermost error first.
+    /// The errors are type-erased, so see `exception_class` for their classes.
+    pub fn chain_from_error(error: &(dyn StdError + 'static)) -> Vec<Trace> {
+        let mut chain = vec![Trace::with_class(exception_class(error), error)];
+        chain.extend(sources(error).map(|e| Trace::with_class(exception_class(e), e)));
+        chain
+    }
+
+    fn with_class(class: &str, error: &dyn StdError) -> Trace {
+        let exception = Exception::builder()
+            .class(class)
+            .message(error.to_string())
+            .build();
+        Trace::builder().exception(exception).build()
+    }
+}
+
+impl Body {
+    /// A `TraceBody` for an error without causes, otherwise a
+    /// `TraceChainBody` with one trace per cause.
+    pub fn from_error<E: StdError + 'static>(error: &E) -> Body {
+        let mut chain = vec![Trace::from_error(error)];
+        chain.extend(Trace::chain_from_error(error).into_iter().skip(1));
+        Body::from_chain(chain)
+    }
+
+    pub fn from_dyn_error(error: &(dyn StdError + 'static)) -> Body {
+        Body::from_chain(Trace::chain_from_error(error))
+    }
+
+    /// Convert an `anyhow::Error`, attaching its captured backtrace to the
+    /// outermost trace.
+    #[cfg(feature = "anyhow")]
+    pub fn from_anyhow(error: &anyhow::Error) -> Body {
+        let mut chain: Vec<Trace> = error
+            .chain()
+            .map(|e| Trace::with_class(exception_class(e), e))
+            .collect();
+        chain[0].frames = crate::panic::frames_from_backtrace(&error.backtrace().to_string());
+        Body::from_chain(chain)
+    }
+
+    #[cfg(feature = "eyre")]
+    pub fn from_eyre(report: &eyre::Report) -> Body {
+        let chain = report
+            .chain()
+            .map(|e| Trace::with_class(exception_class(e), e))
+            .collect();
+        Body::from_chain(chain)
+    }
+
+    /// The message, or the outermost exception's message for traces.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Body::MessageBody { message, .. } => Some(&message.body),
+            Body::TraceBody { trace, .. } => trace.exception.message.as_deref(),
+            Body::TraceChainBody { trace_chain, .. } => trace_chain
+                .first()
+                .and_then(|trace| trace.exception.message.as_deref()),
+            Body::CrashReportBody { crash_report, .. } => Some(&crash_report.raw),
+            Body::Unset => None,
+        }
+    }
+
+    /// The exception classes in the body, outermost first.
+    pub fn exception_classes(&self) -> Vec<&str> {
+        match self {
+            Body::TraceBody { trace, .. } => vec![trace.exception.class.as_str()],
+            Body::TraceChainBody { trace_chain, .. } => trace_chain
+                .iter()
+                .map(|trace| trace.exception.class.as_str())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn from_chain(mut chain: Vec<Trace>) -> Body {
+        if chain.len() == 1 {
+            Body::builder().trace(chain.remove(0)).build()
+        } else {
+            Body::builder().trace_chain(chain).build()
+        }
+    }
+}
+
+fn sources<'a>(error: &'a dyn StdError) -> impl Iterator<Item = &'a (dyn StdError + 'static)> {
+    let mut next = error.source();
+    std::iter::from_fn(move || {
+        let current = next?;
+        next = current.source();
+        Some(current)
+    })
+}
+
+/// The class of an error known only as `dyn Error`, whose type name can't be
+/// recovered. Common std errors are recognized by downcasting and get their
+/// type name, like errors of a known type do; any other gets `UNKNOWN_CLASS`.
+fn exception_class(error: &(dyn StdError + 'static)) -> &'static str {
+    macro_rules! known {
+        ($($ty:ty),*) => {
+            $(if error.is::<$ty>() {
+                return any::type_name::<$ty>();
+            })*
+        };
+    }
+    known!(
+        std::io::Error,
+        std::fmt::Error,
+        std::num::ParseIntError,
+        std::num::ParseFloatError,
+        std::num::TryFromIntError,
+        std::str::ParseBoolError,
+        std::str::Utf8Error,
+        std::string::FromUtf8Error,
+        std::net::AddrParseError,
+        std::time::SystemTimeError
+    );
+    UNKNOWN_CLASS
+}
+
+/// The class of type-erased errors `exception_class` doesn't recognize.
+const UNKNOWN_CLASS: &str = "Error";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct ConfigError {
+        source: std::num::ParseIntError,
+    }
+
+    impl fmt::Display for ConfigError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "invalid port")
+        }
+    }
+
+    impl StdError for ConfigError {
+        fn source(&self) -> Option<&(dyn StdError + 'static)> {
+            Some(&self.source)
+        }
+    }
+
+    fn config_error() -> ConfigError {
+        ConfigError {
+            source: "http".parse::<u16>().unwrap_err(),
+        }
+    }
+
+    #[test]
+    fn test_chain_from_error() {
+        let chain = Trace::chain_from_error(&config_error());
+
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].exception.class, UNKNOWN_CLASS);
+        assert_eq!(chain[0].exception.message.as_deref(), Some("invalid port"));
+        assert_eq!(chain[1].exception.class, "core::num::error::ParseIntError");
+        assert_eq!(
+            chain[1].exception.message.as_deref(),
+            Some("invalid digit found in string")
+        );
+    }
+
+    #[test]
+    fn test_body_from_error_uses_type_name() {
+        match Body::from_error(&config_error()) {
+            Body::TraceChainBody { trace_chain, .. } => {
+                assert_eq!(
+                    trace_chain[0].exception.class,
+                    "rollbar_rust::trace::tests::ConfigError"
+                );
+                assert_eq!(
+                    trace_chain[1].exception.class,
+                    "core::num::error::ParseIntError"
+                );
+            }
+            _ => panic!("expected a trace chain body"),
+        }
+    }
+
+    #[test]
+    fn test_io_error_class_is_its_type_name() {
+        let error = std::io::Error::other("disk full");
+
+        let chain = Trace::chain_from_error(&error);
+        assert_eq!(chain[0].exception.class, "std::io::error::Error");
+    }
+
+    #[test]
+    fn test_body_from_error_without_source() {
+        let error = "x".parse::<i32>().unwrap_err();
+
+        match Body::from_dyn_error(&error) {
+            Body::TraceBody { trace, .. } => {
+                assert_eq!(trace.exception.class, "core::num::error::ParseIntError");
+            }
+            _ => panic!("expected a trace body"),
+        }
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_body_from_anyhow() {
+        let error = anyhow::Error::new(config_error()).context("loading config");
+
+        match Body::from_anyhow(&error) {
+            Body::TraceChainBody { trace_chain, .. } => {
+                assert_eq!(trace_chain.len(), 3);
+                assert_eq!(
+                    trace_chain[0].exception.message.as_deref(),
+                    Some("loading config")
+                );
+                assert_eq!(trace_chain[0].exception.class, UNKNOWN_CLASS);
+                assert_eq!(
+                    trace_chain[2].exception.class,
+                    "core::num::error::ParseIntError"
+                );
+            }
+            _ => panic!("expected a trace chain body"),
+        }
+    }
+}
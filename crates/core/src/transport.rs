@@ -1,19 +1,54 @@
+#[cfg(feature = "testing")]
+mod memory;
 #[cfg(not(target_arch = "wasm32"))]
 mod native;
+#[cfg(not(target_arch = "wasm32"))]
+mod ndjson;
+#[cfg(not(target_arch = "wasm32"))]
+mod queue;
+#[cfg(not(target_arch = "wasm32"))]
+mod rate_limit;
+#[cfg(not(target_arch = "wasm32"))]
+mod retry;
+#[cfg(not(target_arch = "wasm32"))]
+mod spool;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) mod stand_in;
+mod stats;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use native::http_client;
 #[cfg(not(target_arch = "wasm32"))]
 pub use native::HttpTransport;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use retry::retry_after;
 
 #[cfg(target_arch = "wasm32")]
 pub use wasm::HttpTransport;
 
-use crate::configuration::Configuration;
+#[cfg(not(target_arch = "wasm32"))]
+pub use ndjson::{FileTransport, StdoutTransport};
+
+#[cfg(feature = "testing")]
+pub use memory::{BodyKind, MemoryTransport};
 
-pub const QUEUE_DEPTH: usize = 50;
+use crate::configuration::Configuration;
+use crate::fingerprint::Fingerprinter;
+use crate::sample::Sampler;
+use crate::scrub::Scrubber;
+use crate::truncate::Truncator;
 
+use futures::channel::oneshot;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::Duration;
 
 use crate::types::Item;
@@ -21,7 +56,266 @@ use crate::types::Item;
 pub trait Transport: Send + Sync + 'static {
     fn send(&self, item: Item);
 
+    /// Send `item` and resolve once the API has accepted or rejected it.
+    ///
+    /// Transports that cannot report the outcome hand the item to `send` and
+    /// resolve straight away with an empty `Sent`.
+    fn send_async(&self, item: Item) -> SendFuture {
+        self.send(item);
+        SendFuture::ready(Ok(Sent::default()))
+    }
+
     fn config(&self) -> &Configuration;
 
+    /// A snapshot of what the transport has been doing.
+    fn stats(&self) -> TransportStats {
+        TransportStats::default()
+    }
+
+    /// Wait up to `timeout` for the items sent so far to be delivered,
+    /// leaving the transport open. Returns false if some are still pending.
+    fn flush(&self, _timeout: Duration) -> bool {
+        true
+    }
+
     fn shutdown(&self, timeout: Duration) -> bool;
 }
+
+/// The occurrence the API created for an item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sent {
+    pub id: Option<String>,
+    pub uuid: Option<String>,
+}
+
+/// Why an item was not accepted by the API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    /// The API answered with an error status.
+    Api {
+        status: u16,
+        err: Option<u8>,
+        message: Option<String>,
+        retry_after: Option<Duration>,
+    },
+    /// The request timed out.
+    Timeout,
+    /// The request could not be made or the connection failed.
+    Network(String),
+    /// The item could not be turned into a request.
+    Request(String),
+    /// The item could not be written out.
+    Io(String),
+    /// The API's answer could not be understood.
+    Response(String),
+    /// The item was dropped because of rate limiting.
+    RateLimited,
+    /// The transport dropped the item before it was sent.
+    Dropped,
+    /// A `BeforeSend` hook or sampling dropped the item, or reporting is
+    /// disabled.
+    Ignored,
+}
+
+impl SendError {
+    /// Network failures, timeouts, 429s and 5xx responses are worth another
+    /// attempt; anything else will fail the same way again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SendError::Api { status, .. } => *status == 429 || *status >= 500,
+            SendError::Timeout | SendError::Network(_) => true,
+            SendError::Request(_)
+            | SendError::Io(_)
+            | SendError::Response(_)
+            | SendError::RateLimited
+            | SendError::Dropped
+            | SendError::Ignored => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            SendError::Timeout
+        } else if err.is_builder() {
+            SendError::Request(err.to_string())
+        } else {
+            SendError::Network(err.to_string())
+        }
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Api {
+                status,
+                message: Some(message),
+                ..
+            } => write!(f, "HTTP {}: {}", status, message),
+            SendError::Api { status, .. } => write!(f, "HTTP {}", status),
+            SendError::Timeout => write!(f, "request timed out"),
+            SendError::Network(err) => write!(f, "{}", err),
+            SendError::Request(err) => write!(f, "invalid request: {}", err),
+            SendError::Io(err) => write!(f, "{}", err),
+            SendError::Response(err) => write!(f, "invalid response: {}", err),
+            SendError::RateLimited => write!(f, "rate limited"),
+            SendError::Dropped => write!(f, "item dropped by the transport"),
+            SendError::Ignored => write!(f, "item filtered out before sending"),
+        }
+    }
+}
+
+impl StdError for SendError {}
+
+/// Resolves to the outcome of sending an item.
+///
+/// Does not depend on any particular executor. Resolves to
+/// `SendError::Dropped` if the transport goes away without sending the item.
+#[derive(Debug)]
+pub struct SendFuture(SendFutureState);
+
+#[derive(Debug)]
+enum SendFutureState {
+    Ready(Option<Result<Sent, SendError>>),
+    Pending(oneshot::Receiver<Result<Sent, SendError>>),
+}
+
+impl SendFuture {
+    pub fn ready(result: Result<Sent, SendError>) -> Self {
+        SendFuture(SendFutureState::Ready(Some(result)))
+    }
+
+    pub(crate) fn channel() -> (oneshot::Sender<Result<Sent, SendError>>, Self) {
+        let (tx, rx) = oneshot::channel();
+        (tx, SendFuture(SendFutureState::Pending(rx)))
+    }
+}
+
+impl Future for SendFuture {
+    type Output = Result<Sent, SendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.0 {
+            SendFutureState::Ready(result) => {
+                Poll::Ready(result.take().expect("SendFuture polled after completion"))
+            }
+            SendFutureState::Pending(rx) => Pin::new(rx)
+                .poll(cx)
+                .map(|result| result.unwrap_or(Err(SendError::Dropped))),
+        }
+    }
+}
+
+/// How many items a transport has dropped, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedItems {
+    /// Dropped because the queue was full or the transport shut down.
+    pub overflow: u64,
+    /// Dropped because of client-side or API rate limiting.
+    pub rate_limited: u64,
+    /// Dropped by sampling.
+    pub sampled: u64,
+}
+
+/// Counters and recent history of a transport, for health checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransportStats {
+    /// Items waiting to be sent.
+    pub queued: usize,
+    /// Items currently being sent, including retries.
+    pub in_flight: usize,
+    /// Items the API accepted.
+    pub sent: u64,
+    /// Items that could not be delivered.
+    pub failed: u64,
+    /// Requests that were retried.
+    pub retried: u64,
+    pub dropped: DroppedItems,
+    pub last_error: Option<String>,
+    /// When an item was last accepted, in milliseconds since the epoch.
+    pub last_success: Option<u64>,
+    pub latency_p50_ms: Option<u64>,
+    pub latency_p99_ms: Option<u64>,
+}
+
+/// The body the API answers with.
+#[derive(Debug, Deserialize)]
+struct Response {
+    err: u8,
+    result: Option<Success>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Success {
+    id: Option<String>,
+    uuid: Option<String>,
+}
+
+/// Turn the API's answer into the outcome of a send.
+pub(crate) async fn read_response(
+    resp: reqwest::Response,
+    retry_after: Option<Duration>,
+) -> Result<Sent, SendError> {
+    let status = resp.status();
+    let response: Option<Response> = resp.json().await.ok();
+    if status.is_success() {
+        let result = response.and_then(|r| r.result);
+        Ok(Sent {
+            id: result.as_ref().and_then(|r| r.id.clone()),
+            uuid: result.and_then(|r| r.uuid),
+        })
+    } else {
+        Err(SendError::Api {
+            status: status.as_u16(),
+            err: response.as_ref().map(|r| r.err),
+            message: response.and_then(|r| r.message),
+            retry_after,
+        })
+    }
+}
+
+/// The stages every item goes through before a transport queues it.
+#[derive(Debug, Clone)]
+pub(crate) struct Pipeline {
+    fingerprinter: Fingerprinter,
+    sampler: Sampler,
+    scrubber: Scrubber,
+    truncator: Truncator,
+    sampled: Arc<AtomicU64>,
+}
+
+impl Pipeline {
+    pub(crate) fn new(configuration: &Configuration) -> Self {
+        Pipeline {
+            fingerprinter: Fingerprinter::new(configuration),
+            sampler: Sampler::new(configuration),
+            scrubber: Scrubber::new(configuration),
+            truncator: Truncator::new(configuration),
+            sampled: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Returns false if the item was sampled out and should be dropped.
+    pub(crate) fn process(&self, item: &mut Item) -> bool {
+        self.fingerprinter.fingerprint_item(item);
+        if !self.sampler.sample_item(item) {
+            self.sampled.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.scrubber.scrub_item(item);
+        if !self.truncator.truncate_item(item) {
+            warn!("item is still larger than the maximum size after truncation");
+        }
+        true
+    }
+
+    /// How many items were sampled out.
+    pub(crate) fn sampled(&self) -> u64 {
+        self.sampled.load(Ordering::Relaxed)
+    }
+}
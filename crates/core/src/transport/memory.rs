@@ -0,0 +1,250 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{DroppedItems, Pipeline, SendError, SendFuture, Sent, Transport, TransportStats};
+use crate::configuration::Configuration;
+use crate::types::{Body, Item, Level};
+
+/// The kind of body an item carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+    Trace,
+    TraceChain,
+    Message,
+    CrashReport,
+}
+
+impl BodyKind {
+    pub fn of(body: &Body) -> Option<BodyKind> {
+        match body {
+            Body::Unset => None,
+            Body::TraceBody { .. } => Some(BodyKind::Trace),
+            Body::TraceChainBody { .. } => Some(BodyKind::TraceChain),
+            Body::MessageBody { .. } => Some(BodyKind::Message),
+            Body::CrashReportBody { .. } => Some(BodyKind::CrashReport),
+        }
+    }
+}
+
+/// A transport that records items in memory instead of sending them.
+///
+/// Items go through the same pipeline as with `HttpTransport`. Clones share
+/// the recording, so keep a clone around after handing the transport to
+/// `Rollbar::with_transport`:
+///
+/// ```
+/// use rollbar_rust::types::Level;
+/// use rollbar_rust::{Configuration, MemoryTransport, Rollbar};
+///
+/// let configuration = Configuration {
+///     access_token: Some("abc123".to_owned()),
+///     ..Configuration::default()
+/// };
+/// let transport = MemoryTransport::new(configuration);
+/// let rollbar = Rollbar::with_transport(transport.clone());
+///
+/// rollbar.log(Level::Error, "payment failed").unwrap();
+///
+/// transport.assert_message(Level::Error, "payment failed");
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+    configuration: Arc<Configuration>,
+    pipeline: Pipeline,
+    items: Arc<Mutex<Vec<Item>>>,
+    sent: Arc<AtomicU64>,
+}
+
+impl MemoryTransport {
+    pub fn new(configuration: Configuration) -> Self {
+        MemoryTransport {
+            pipeline: Pipeline::new(&configuration),
+            configuration: Arc::new(configuration),
+            items: Arc::new(Mutex::new(Vec::new())),
+            sent: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Every item sent so far, oldest first.
+    pub fn items(&self) -> Vec<Item> {
+        self.items.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget the items sent so far. `stats` keeps counting them.
+    pub fn clear(&self) {
+        self.items.lock().unwrap().clear();
+    }
+
+    pub fn filter<P: Fn(&Item) -> bool>(&self, predicate: P) -> Vec<Item> {
+        let items = self.items.lock().unwrap();
+        items
+            .iter()
+            .filter(|item| predicate(item))
+            .cloned()
+            .collect()
+    }
+
+    pub fn with_level(&self, level: Level) -> Vec<Item> {
+        self.filter(|item| item.data.level.as_ref() == Some(&level))
+    }
+
+    pub fn with_body_kind(&self, kind: BodyKind) -> Vec<Item> {
+        self.filter(|item| BodyKind::of(&item.data.body) == Some(kind))
+    }
+
+    /// Items whose message, or exception message for traces, contains
+    /// `needle`.
+    pub fn with_message(&self, needle: &str) -> Vec<Item> {
+        self.filter(|item| item.data.body.message().is_some_and(|m| m.contains(needle)))
+    }
+
+    /// Items with an exception of class `class` anywhere in their trace chain.
+    pub fn with_exception_class(&self, class: &str) -> Vec<Item> {
+        self.filter(|item| item.data.body.exception_classes().contains(&class))
+    }
+
+    #[track_caller]
+    pub fn assert_count(&self, count: usize) {
+        let items = self.items();
+        assert!(
+            items.len() == count,
+            "expected {} items, got {}: {:#?}",
+            count,
+            items.len(),
+            items
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_empty(&self) {
+        self.assert_count(0);
+    }
+
+    /// Assert that an item with `level` and a message containing `needle`
+    /// was sent.
+    #[track_caller]
+    pub fn assert_message(&self, level: Level, needle: &str) {
+        let found = self
+            .with_message(needle)
+            .iter()
+            .any(|item| item.data.level.as_ref() == Some(&level));
+        assert!(
+            found,
+            "no {:?} item with a message containing {:?} in {:#?}",
+            level,
+            needle,
+            self.items()
+        );
+    }
+
+    /// Assert that an item with an exception of class `class` was sent.
+    #[track_caller]
+    pub fn assert_exception_class(&self, class: &str) {
+        assert!(
+            !self.with_exception_class(class).is_empty(),
+            "no item with exception class {:?} in {:#?}",
+            class,
+            self.items()
+        );
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&self, item: Item) {
+        self.send_async(item);
+    }
+
+    fn send_async(&self, mut item: Item) -> SendFuture {
+        if !self.pipeline.process(&mut item) {
+            return SendFuture::ready(Err(SendError::Ignored));
+        }
+        let uuid = item.data.uuid.clone();
+        self.items.lock().unwrap().push(item);
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        SendFuture::ready(Ok(Sent { id: None, uuid }))
+    }
+
+    fn config(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    fn stats(&self) -> TransportStats {
+        TransportStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            dropped: DroppedItems {
+                sampled: self.pipeline.sampled(),
+                ..DroppedItems::default()
+            },
+            ..TransportStats::default()
+        }
+    }
+
+    fn shutdown(&self, _timeout: Duration) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Rollbar;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct PaymentError;
+
+    impl fmt::Display for PaymentError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "card declined")
+        }
+    }
+
+    impl std::error::Error for PaymentError {}
+
+    fn rollbar() -> (Rollbar, MemoryTransport) {
+        let transport = MemoryTransport::new(Configuration {
+            access_token: Some("abc123".to_owned()),
+            ..Configuration::default()
+        });
+        (Rollbar::with_transport(transport.clone()), transport)
+    }
+
+    #[test]
+    fn test_records_items() {
+        let (rollbar, transport) = rollbar();
+        transport.assert_empty();
+
+        rollbar.info("started").unwrap();
+        rollbar.error(&PaymentError).unwrap();
+
+        transport.assert_count(2);
+        transport.assert_message(Level::Info, "started");
+        transport.assert_message(Level::Error, "declined");
+        transport.assert_exception_class("rollbar_rust::transport::memory::tests::PaymentError");
+        assert_eq!(transport.with_level(Level::Info).len(), 1);
+        assert_eq!(transport.with_body_kind(BodyKind::Message).len(), 1);
+        assert_eq!(transport.with_body_kind(BodyKind::Trace).len(), 1);
+
+        transport.clear();
+        assert!(transport.is_empty());
+        assert_eq!(transport.stats().sent, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "no Warning item")]
+    fn test_assert_message_fails() {
+        let (rollbar, transport) = rollbar();
+        rollbar.info("started").unwrap();
+
+        transport.assert_message(Level::Warning, "started");
+    }
+}
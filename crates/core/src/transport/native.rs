@@ -1,115 +1,241 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use tokio::{sync::mpsc, runtime::Runtime};
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use tokio::runtime::Runtime;
+use tokio::sync::watch;
 
-use crate::configuration::Configuration;
-use crate::types::Item;
+use super::queue::{Prioritized, Queue};
+use super::rate_limit::RateLimiter;
+use super::retry::{retry_after, RetryPolicy};
+use super::spool::{Record, Spool};
+use super::stats::StatsRecorder;
+use super::{read_response, DroppedItems, Pipeline, SendError, SendFuture, Sent, TransportStats};
+use crate::configuration::{Configuration, ConfigurationError};
+use crate::constants;
+use crate::errors::{self, Error, ErrorKind};
+use crate::telemetry::timestamp_ms;
+use crate::types::{Body, Data, Item, Level, Message};
 
 use reqwest::{Client, Proxy};
 
 #[derive(Debug)]
 pub struct HttpTransport {
-    sender: Mutex<mpsc::Sender<Option<Item>>>,
+    queue: Arc<Queue<Queued>>,
     queue_depth: Arc<Mutex<usize>>,
     signal: Arc<Condvar>,
     shutdown: Arc<AtomicBool>,
+    /// When `shutdown` was asked to be done by, for retries to give up.
+    deadline: watch::Sender<Option<Instant>>,
     configuration: Arc<Configuration>,
-    runtime: Option<Runtime>,
+    pipeline: Pipeline,
+    rate_limiter: Arc<RateLimiter>,
+    spool: Option<Arc<Spool>>,
+    stats: Arc<StatsRecorder>,
+    /// Runs the sender task for as long as the transport lives.
+    _runtime: Runtime,
 }
 
 impl HttpTransport {
-    pub fn new(configuration: Configuration) -> Result<Self, std::io::Error> {
-        let (tx, rx) = mpsc::channel(super::QUEUE_DEPTH);
+    /// Fails if the configuration does not pass `Configuration::validate`
+    /// or the HTTP client, spool or runtime cannot be set up.
+    pub fn new(configuration: Configuration) -> errors::Result<Self> {
+        configuration.validate()?;
+        let queue = Arc::new(Queue::new(&configuration));
         let signal = Arc::new(Condvar::new());
         let shutdown = Arc::new(AtomicBool::new(false));
+        let (deadline, deadline_rx) = watch::channel(None);
 
-        let mut client_builder = Client::builder()
-            .gzip(true)
-            .timeout(Duration::from_secs(configuration.timeout));
-
-        let proxy = configuration
-            .proxy
-            .as_ref()
-            .and_then(|proxy| Proxy::all(proxy).ok());
-
-        if let Some(proxy) = proxy {
-            client_builder = client_builder.proxy(proxy);
-        }
-
-        let client = client_builder.build().unwrap();
+        let client = http_client(&configuration)?;
         #[allow(clippy::mutex_atomic)]
         let queue_depth = Arc::new(Mutex::new(0));
         let endpoint = configuration.endpoint.clone();
-        let runtime = Runtime::new()?;
+        let retry_policy = RetryPolicy::new(&configuration);
+        let rate_limiter = Arc::new(RateLimiter::new(&configuration));
+        let spool = match &configuration.spool_dir {
+            Some(dir) => Some(Arc::new(
+                Spool::open(dir, configuration.spool_max_size).map_err(transport_error)?,
+            )),
+            None => None,
+        };
+        let stats = Arc::new(StatsRecorder::default());
+        let runtime = Runtime::new().map_err(transport_error)?;
+        let configuration = Arc::new(configuration);
 
-        spawn_sender(
+        let sender = Sender {
             endpoint,
             client,
-            rx,
+            retry_policy,
+            rate_limiter: rate_limiter.clone(),
+            spool: spool.clone(),
+            stats: stats.clone(),
+            shutdown: shutdown.clone(),
+            deadline: deadline_rx,
+        };
+        spawn_sender(
+            sender,
+            queue.clone(),
+            configuration.clone(),
             queue_depth.clone(),
             signal.clone(),
-            shutdown.clone(),
             &runtime,
         );
 
-        let sender = Mutex::new(tx);
+        let pipeline = Pipeline::new(&configuration);
 
         Ok(HttpTransport {
-            sender,
+            queue,
             queue_depth,
             signal,
             shutdown,
-            configuration: Arc::new(configuration),
-            runtime: Some(runtime),
+            deadline,
+            configuration,
+            pipeline,
+            rate_limiter,
+            spool,
+            stats,
+            _runtime: runtime,
         })
     }
 }
 
+/// An HTTP client with the configured timeout and proxy.
+pub(crate) fn http_client(configuration: &Configuration) -> errors::Result<Client> {
+    let mut client_builder = Client::builder()
+        .gzip(true)
+        .timeout(Duration::from_secs(configuration.timeout));
+
+    if let Some(proxy) = &configuration.proxy {
+        let proxy =
+            Proxy::all(proxy).map_err(|err| ConfigurationError::InvalidProxy(err.to_string()))?;
+        client_builder = client_builder.proxy(proxy);
+    }
+
+    client_builder.build().map_err(transport_error)
+}
+
+fn transport_error<E: std::fmt::Display>(err: E) -> Error {
+    ErrorKind::Transport(err.to_string()).into()
+}
+
 use super::Transport;
 
-impl Transport for HttpTransport {
-    fn send(&self, item: Item) {
+impl HttpTransport {
+    /// Send `item` exactly as it is, skipping the pipeline, e.g. one that was
+    /// already processed and recorded by a `FileTransport`.
+    pub fn send_processed(&self, item: Item) -> SendFuture {
+        let (reply, future) = SendFuture::channel();
+        self.queue(item, Some(reply));
+        future
+    }
+
+    fn enqueue(&self, mut item: Item, reply: Option<oneshot::Sender<Result<Sent, SendError>>>) {
+        if !self.pipeline.process(&mut item) {
+            if let Some(reply) = reply {
+                reply.send(Err(SendError::Ignored)).ok();
+            }
+            return;
+        }
+        self.queue(item, reply);
+    }
+
+    fn queue(&self, item: Item, reply: Option<oneshot::Sender<Result<Sent, SendError>>>) {
+        if !self.rate_limiter.allow(&item.access_token) {
+            if let Some(reply) = reply {
+                reply.send(Err(SendError::RateLimited)).ok();
+            }
+            return;
+        }
+        let record = self
+            .spool
+            .as_ref()
+            .and_then(|spool| match spool.append(&item) {
+                Ok(record) => Some(record),
+                Err(err) => {
+                    warn!("Failed to spool item: {}", err);
+                    None
+                }
+            });
+        // While the API blocks the token, spooled items wait for it to lift
+        // and the rest are dropped.
+        if self.rate_limiter.is_blocked(&item.access_token) {
+            match (&self.spool, record) {
+                (Some(spool), Some(record)) => {
+                    debug!("Spooled item while rate limited by the API");
+                    spool.release(record);
+                }
+                _ => {
+                    debug!("Dropped item while rate limited by the API");
+                    self.rate_limiter.drop_blocked();
+                }
+            }
+            if let Some(reply) = reply {
+                reply.send(Err(SendError::RateLimited)).ok();
+            }
+            return;
+        }
         *self.queue_depth.lock().unwrap() += 1;
-        if self.sender.lock().unwrap().try_send(Some(item)).is_err() {
+        let queued = Queued {
+            item,
+            record,
+            reply,
+        };
+        self.stats.queued();
+        if let Some(dropped) = self.queue.push(queued) {
+            debug!("Transport queue is full, dropping an item");
+            self.stats.unqueued();
             *self.queue_depth.lock().unwrap() -= 1;
+            if let (Some(spool), Some(record)) = (&self.spool, dropped.record) {
+                spool.release(record);
+            }
         }
     }
 
+    /// How many items this transport has dropped so far.
+    pub fn dropped_items(&self) -> DroppedItems {
+        DroppedItems {
+            overflow: self.queue.dropped(),
+            rate_limited: self.rate_limiter.dropped(),
+            sampled: self.pipeline.sampled(),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(&self, item: Item) {
+        self.enqueue(item, None);
+    }
+
+    fn send_async(&self, item: Item) -> SendFuture {
+        let (reply, future) = SendFuture::channel();
+        self.enqueue(item, Some(reply));
+        future
+    }
+
     fn config(&self) -> &Configuration {
         &self.configuration
     }
 
+    fn stats(&self) -> TransportStats {
+        self.stats.snapshot(self.dropped_items())
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        let guard = self.queue_depth.lock().unwrap();
+        let (_guard, result) = self
+            .signal
+            .wait_timeout_while(guard, timeout, |depth| *depth > 0)
+            .unwrap();
+        !result.timed_out()
+    }
+
     fn shutdown(&self, timeout: Duration) -> bool {
-        if let Some(runtime) = &self.runtime {
-            runtime.block_on(async {
-                debug!("http transport shutdown");
-                {
-                    let guard = self.queue_depth.lock().unwrap();
-                    if *guard == 0 {
-                        if let Ok(sender) = self.sender.lock() {
-                            sender.send(None).await.ok();
-                        }
-                        return true;
-                    } else if let Ok(sender) = self.sender.lock() {
-                        match sender.send(None).await {
-                            Err(_) => {}
-                            Ok(_) => {
-                                return self.signal.wait_timeout(guard, timeout).is_ok();
-                            }
-                        }
-                    }
-                }
-                if let Ok(sender) = self.sender.lock() {
-                    sender.send(None).await.ok();
-                }
-                let guard = self.queue_depth.lock().unwrap();
-                self.signal.wait_timeout(guard, timeout).is_ok()
-            })
-        } else {
-            false
-        }
+        debug!("http transport shutdown");
+        self.deadline.send(Some(Instant::now() + timeout)).ok();
+        self.queue.close();
+        self.flush(timeout)
     }
 }
 
@@ -117,82 +243,589 @@ impl Drop for HttpTransport {
     fn drop(&mut self) {
         debug!("http transport drop");
         self.shutdown.store(true, Ordering::SeqCst);
-        if let Some(runtime) = &self.runtime {
-            runtime.block_on(async {
-                if let Ok(sender) = self.sender.lock() {
-                    sender.send(None).await.ok();
-                }
-            });
-        }
+        self.deadline.send(Some(Instant::now())).ok();
+        self.queue.close();
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Response {
-    err: u8,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    result: Option<Success>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    message: Option<String>,
+/// How often to retry spooled items while no new items are coming in.
+const DRAIN_INTERVAL: Duration = Duration::from_secs(30);
+/// The least time between two reports of dropped items.
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An item on its way to the sender, with its place in the spool if any.
+#[derive(Debug)]
+struct Queued {
+    item: Item,
+    record: Option<Record>,
+    reply: Option<oneshot::Sender<Result<Sent, SendError>>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Success {
-    id: Option<String>,
-    uuid: Option<String>,
+impl Prioritized for Queued {
+    fn level(&self) -> Level {
+        self.item.data.level.clone().unwrap_or(Level::Error)
+    }
 }
 
-fn spawn_sender(
+struct Sender {
     endpoint: String,
     client: Client,
-    mut rx: mpsc::Receiver<Option<Item>>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Arc<RateLimiter>,
+    spool: Option<Arc<Spool>>,
+    stats: Arc<StatsRecorder>,
+    shutdown: Arc<AtomicBool>,
+    deadline: watch::Receiver<Option<Instant>>,
+}
+
+impl Sender {
+    async fn post(&self, item: &Item) -> Result<Sent, SendError> {
+        if self.rate_limiter.is_blocked(&item.access_token) {
+            return Err(SendError::RateLimited);
+        }
+        let started = timestamp_ms();
+        let resp = self
+            .client
+            .post(&self.endpoint)
+            .json(item)
+            .header("X-Rollbar-Access-Token", item.access_token.as_str())
+            .send()
+            .await;
+        self.stats.latency(started);
+        let resp = resp?;
+
+        let status = resp.status().as_u16();
+        self.rate_limiter
+            .update(&item.access_token, status, resp.headers());
+        let retry_after = retry_after(resp.headers());
+        read_response(resp, retry_after).await
+    }
+
+    async fn post_with_retry(&self, item: &Item) -> Result<Sent, SendError> {
+        let policy = &self.retry_policy;
+        let mut attempt = 0;
+        loop {
+            let err = match self.post(item).await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            if !err.is_transient()
+                || attempt >= policy.max_retries
+                || self.shutdown.load(Ordering::SeqCst)
+            {
+                return Err(err);
+            }
+            let retry_after = match &err {
+                SendError::Api { retry_after, .. } => *retry_after,
+                _ => None,
+            };
+            let delay = policy.delay(attempt, retry_after);
+            debug!("Retrying item in {:?} after: {}", delay, err);
+            if !self.wait_to_retry(delay).await {
+                debug!("Not retrying item, the transport is shutting down");
+                return Err(err);
+            }
+            self.stats.retried();
+            attempt += 1;
+        }
+    }
+
+    /// Sleep for `delay` before a retry. Returns false, as soon as it is
+    /// known, if the transport has to be shut down before then.
+    async fn wait_to_retry(&self, delay: Duration) -> bool {
+        let wake = Instant::now() + delay;
+        let mut deadline = self.deadline.clone();
+        loop {
+            if let Some(deadline) = *deadline.borrow_and_update() {
+                if wake > deadline {
+                    return false;
+                }
+            }
+            let sleep = tokio::time::sleep_until(wake.into());
+            let changed = deadline.changed();
+            futures::pin_mut!(sleep, changed);
+            match future::select(sleep, changed).await {
+                Either::Left(_) => return true,
+                Either::Right((Ok(()), _)) => {}
+                Either::Right((Err(_), _)) => return false,
+            }
+        }
+    }
+
+    /// Returns false if the item could not be delivered for now and should
+    /// be tried again later.
+    async fn deliver(
+        &self,
+        item: &Item,
+        record: Option<Record>,
+        reply: Option<oneshot::Sender<Result<Sent, SendError>>>,
+    ) -> bool {
+        self.stats.started();
+        let result = self.post_with_retry(item).await;
+        self.stats.finished(&result);
+        // While the API blocks the token, spooled items wait for it to lift.
+        let delivered = !matches!(
+            &result,
+            Err(err) if err.is_transient() || (*err == SendError::RateLimited && record.is_some())
+        );
+        match &result {
+            Ok(Sent { id: Some(id), .. }) => {
+                info!("Item sent: {}", id);
+            }
+            Ok(_) => {
+                info!("Error deserializing response, but successfully sent item");
+            }
+            Err(SendError::RateLimited) if record.is_some() => {
+                debug!("Keeping spooled item while rate limited by the API");
+            }
+            Err(SendError::RateLimited) => {
+                self.rate_limiter.drop_blocked();
+                debug!("Dropped item while rate limited by the API");
+            }
+            Err(err) => {
+                warn!("Failed to send item: {}", err);
+            }
+        }
+
+        if let (Some(spool), Some(record)) = (&self.spool, record) {
+            if delivered {
+                spool.ack(record);
+            } else {
+                spool.release(record);
+            }
+        }
+        if let Some(reply) = reply {
+            reply.send(result).ok();
+        }
+        delivered
+    }
+
+    /// Make spooled items durable, off the threads that enqueue them.
+    async fn sync_spool(&self) {
+        if let Some(spool) = self.spool.clone() {
+            if let Ok(Err(err)) = tokio::task::spawn_blocking(move || spool.sync()).await {
+                warn!("Failed to sync spooled items: {}", err);
+            }
+        }
+    }
+
+    fn has_backlog(&self) -> bool {
+        self.spool.as_ref().is_some_and(|spool| spool.has_backlog())
+    }
+
+    /// Resend spooled items, stopping at the first one that can't be
+    /// delivered.
+    async fn drain_spool(&self) {
+        let spool = match &self.spool {
+            Some(spool) => spool,
+            None => return,
+        };
+        self.sync_spool().await;
+        let mut pending = match spool.drain() {
+            Ok(pending) => pending.into_iter(),
+            Err(err) => {
+                warn!("Failed to read spooled items: {}", err);
+                return;
+            }
+        };
+        debug!("Draining {} spooled items", pending.len());
+        for (record, item) in pending.by_ref() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                spool.release(record);
+                break;
+            }
+            if !self.deliver(&item, Some(record), None).await {
+                break;
+            }
+        }
+        pending.for_each(|(record, _)| spool.release(record));
+    }
+}
+
+/// A warning item telling the project how many items were dropped because
+/// the queue was full.
+fn dropped_items_report(configuration: &Configuration, count: u64) -> Option<Item> {
+    let access_token = configuration.access_token.clone()?;
+    let message = Message::builder()
+        .body(format!(
+            "Dropped {} items because the transport queue was full",
+            count
+        ))
+        .build();
+    let data = Data {
+        body: Body::builder().message(message).build(),
+        level: Some(Level::Warning),
+        environment: configuration.environment.clone(),
+        platform: Some(constants::PLATFORM.to_owned()),
+        notifier: Some(constants::NOTIFIER.clone()),
+        ..Data::default()
+    };
+    Some(
+        Item::builder()
+            .access_token(access_token)
+            .data(data)
+            .build(),
+    )
+}
+
+fn spawn_sender(
+    sender: Sender,
+    queue: Arc<Queue<Queued>>,
+    configuration: Arc<Configuration>,
     queue_depth: Arc<Mutex<usize>>,
     signal: Arc<Condvar>,
-    shutdown: Arc<AtomicBool>,
     runtime: &Runtime,
 ) {
     runtime.spawn(async move {
-        while let Some(item) = rx.recv().await {
-            let item = match item.clone() {
-                Some(item) => item,
-                None => break
+        let mut reported = 0;
+        let mut last_report: Option<Instant> = None;
+
+        sender.drain_spool().await;
+        loop {
+            let queued = match tokio::time::timeout(DRAIN_INTERVAL, queue.pop()).await {
+                Ok(Some(queued)) => {
+                    sender.stats.unqueued();
+                    Some(queued)
+                }
+                Ok(None) => break,
+                Err(_) => None,
             };
 
-            if shutdown.load(Ordering::SeqCst) {
+            if sender.shutdown.load(Ordering::SeqCst) {
                 let mut size = queue_depth.lock().unwrap();
                 *size = 0;
                 signal.notify_all();
                 break;
             }
 
-            let access_token = item.access_token.as_str();
-
-            match client
-                .post(endpoint.as_str())
-                .json(&item)
-                .header("X-Rollbar-Access-Token", access_token)
-                .send().await
-            {
-                Ok(resp) => {
-                    let resp = resp.json().await;
-                    let resp: Option<Response> = resp.ok();
-                    if let Some(r) = resp {
-                        info!("Item sent\n{}", serde_json::to_string_pretty(&r).unwrap());
-                    } else {
-                        info!("Error deserializing response, but successfully sent item");
+            let delivered = match queued {
+                Some(queued) => {
+                    if queued.record.is_some() {
+                        sender.sync_spool().await;
                     }
+                    let delivered = sender
+                        .deliver(&queued.item, queued.record, queued.reply)
+                        .await;
+                    let mut size = queue_depth.lock().unwrap();
+                    *size -= 1;
+                    if *size == 0 {
+                        signal.notify_all();
+                    }
+                    delivered
                 }
-                Err(err) => {
-                    info!("Failed to send item: {}", err);
-                }
+                None => true,
+            };
+            if delivered && sender.has_backlog() {
+                sender.drain_spool().await;
             }
 
-            let mut size = queue_depth.lock().unwrap();
-            *size -= 1;
-            if *size == 0 {
-                signal.notify_all();
+            let dropped = queue.dropped();
+            let due = match last_report {
+                Some(last) => last.elapsed() >= REPORT_INTERVAL,
+                None => true,
+            };
+            if configuration.report_dropped_items && dropped > reported && due {
+                if let Some(item) = dropped_items_report(&configuration, dropped - reported) {
+                    sender.deliver(&item, None, None).await;
+                }
+                reported = dropped;
+                last_report = Some(Instant::now());
             }
+            sender.rate_limiter.report_drops(false);
         }
+        sender.rate_limiter.report_drops(true);
         info!("send thread shutdown!");
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::stand_in::{self, StandIn};
+    use super::*;
+    use std::collections::HashMap;
+
+    fn transport(server: &StandIn, max_retries: u32) -> HttpTransport {
+        HttpTransport::new(Configuration {
+            endpoint: server.url.clone(),
+            access_token: Some("abc123".to_owned()),
+            max_retries,
+            retry_base_delay_ms: 1,
+            retry_max_delay_ms: 5,
+            ..Configuration::default()
+        })
+        .unwrap()
+    }
+
+    fn item() -> Item {
+        let message = Message::builder().body("hello").build();
+        let data = Data::builder()
+            .body(Body::builder().message(message).build())
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    #[test]
+    fn test_retries_server_errors() {
+        let server = StandIn::start(vec![
+            stand_in::error(500, "oops"),
+            stand_in::error(503, "busy"),
+            stand_in::ok("1"),
+        ]);
+        let transport = transport(&server, 3);
+
+        transport.send(item());
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_flush_keeps_the_transport_open() {
+        let server = StandIn::start(vec![stand_in::ok("1"), stand_in::ok("2")]);
+        let transport = transport(&server, 0);
+
+        transport.send(item());
+        assert!(transport.flush(Duration::from_secs(5)));
+        assert_eq!(server.requests().len(), 1);
+
+        transport.send(item());
+        assert!(transport.flush(Duration::from_secs(5)));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_send_processed_skips_the_pipeline() {
+        let server = StandIn::start(vec![stand_in::ok("1")]);
+        let transport = HttpTransport::new(Configuration {
+            endpoint: server.url.clone(),
+            access_token: Some("abc123".to_owned()),
+            sample_rates: HashMap::from([(Level::Info, 0.0)]),
+            ..Configuration::default()
+        })
+        .unwrap();
+        let mut item = item();
+        item.data.level = Some(Level::Info);
+        item.data.fingerprint = Some("recorded".to_owned());
+
+        let sent = futures::executor::block_on(transport.send_processed(item));
+        assert!(sent.is_ok());
+        let body: serde_json::Value = serde_json::from_str(&server.requests()[0]).unwrap();
+        assert_eq!(body["data"]["fingerprint"], "recorded");
+    }
+
+    #[test]
+    fn test_does_not_retry_client_errors() {
+        let server = StandIn::start(vec![stand_in::error(400, "bad"), stand_in::ok("1")]);
+        let transport = transport(&server, 3);
+
+        transport.send(item());
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_retries() {
+        let retry_after = vec![("Retry-After", "0".to_owned())];
+        let server = StandIn::start(vec![(429, retry_after, "{}".to_owned())]);
+        let transport = transport(&server, 2);
+
+        transport.send(item());
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_long_retry_delays_give_way_to_shutdown() {
+        let retry_after = vec![("Retry-After", "3600".to_owned())];
+        let server = StandIn::start(vec![(503, retry_after, "{}".to_owned())]);
+        let transport = HttpTransport::new(Configuration {
+            endpoint: server.url.clone(),
+            access_token: Some("abc123".to_owned()),
+            retry_max_delay_ms: 60_000,
+            ..Configuration::default()
+        })
+        .unwrap();
+
+        transport.send(item());
+        std::thread::sleep(Duration::from_millis(200));
+        let started = Instant::now();
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_drops_items_while_rate_limited() {
+        let headers = vec![("X-Rate-Limit-Remaining", "0".to_owned())];
+        let server = StandIn::start(vec![(200, headers, "{}".to_owned())]);
+        let transport = transport(&server, 0);
+
+        transport.send(item());
+        transport.send(item());
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(transport.rate_limiter.dropped(), 1);
+    }
+
+    #[test]
+    fn test_keeps_spooled_items_while_rate_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let headers = vec![("X-Rate-Limit-Remaining", "0".to_owned())];
+        let server = StandIn::start(vec![(200, headers, "{}".to_owned())]);
+        let transport = HttpTransport::new(Configuration {
+            endpoint: server.url.clone(),
+            access_token: Some("abc123".to_owned()),
+            spool_dir: Some(dir.path().to_str().unwrap().to_owned()),
+            ..Configuration::default()
+        })
+        .unwrap();
+
+        transport.send(item());
+        assert!(transport.flush(Duration::from_secs(5)));
+        let reply = transport.send_async(item());
+        assert_eq!(
+            futures::executor::block_on(reply),
+            Err(SendError::RateLimited)
+        );
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        assert_eq!(transport.rate_limiter.dropped(), 0);
+        drop(transport);
+        assert_eq!(server.requests().len(), 1);
+
+        let spool = Spool::open(dir.path(), 1024 * 1024).unwrap();
+        assert_eq!(spool.drain().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_spooled_items_are_sent_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let spooled = |server: &StandIn| {
+            HttpTransport::new(Configuration {
+                endpoint: server.url.clone(),
+                access_token: Some("abc123".to_owned()),
+                max_retries: 0,
+                spool_dir: Some(dir.path().to_str().unwrap().to_owned()),
+                ..Configuration::default()
+            })
+            .unwrap()
+        };
+
+        let down = StandIn::start(vec![stand_in::error(503, "down")]);
+        let transport = spooled(&down);
+        transport.send(item());
+        assert!(transport.shutdown(Duration::from_secs(5)));
+        drop(transport);
+        assert_eq!(down.requests().len(), 1);
+
+        let up = StandIn::start(vec![stand_in::ok("1")]);
+        let _transport = spooled(&up);
+        let drained = (0..500).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            std::fs::read_dir(dir.path()).unwrap().count() == 0
+        });
+        assert!(drained);
+        assert_eq!(up.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_send_async_resolves_to_occurrence() {
+        let server = StandIn::start(vec![stand_in::ok("42")]);
+        let transport = transport(&server, 0);
+
+        let sent = futures::executor::block_on(transport.send_async(item())).unwrap();
+        assert_eq!(sent.id.as_deref(), Some("42"));
+        assert_eq!(sent.uuid.as_deref(), Some("42-uuid"));
+    }
+
+    #[test]
+    fn test_send_async_reports_api_errors() {
+        let server = StandIn::start(vec![stand_in::error(422, "invalid item")]);
+        let transport = transport(&server, 0);
+
+        let err = futures::executor::block_on(transport.send_async(item())).unwrap_err();
+        assert_eq!(
+            err,
+            SendError::Api {
+                status: 422,
+                err: Some(1),
+                message: Some("invalid item".to_owned()),
+                retry_after: None,
+            }
+        );
+        assert!(!err.is_transient());
+    }
+
+    #[test]
+    fn test_rejects_invalid_configuration() {
+        let result = HttpTransport::new(Configuration {
+            access_token: Some("abc123".to_owned()),
+            endpoint: "not a url".to_owned(),
+            ..Configuration::default()
+        });
+
+        match result {
+            Err(Error(
+                ErrorKind::InvalidConfiguration(ConfigurationError::InvalidEndpoint(_)),
+                _,
+            )) => {}
+            other => panic!("expected an invalid endpoint, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_counts_items_dropped_on_overflow() {
+        // Accepts connections but never answers, so the first item stays in
+        // flight and the queue fills up behind it.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let transport = HttpTransport::new(Configuration {
+            endpoint: format!("http://{}/api/1/item/", listener.local_addr().unwrap()),
+            access_token: Some("abc123".to_owned()),
+            queue_depth: 1,
+            max_retries: 0,
+            ..Configuration::default()
+        })
+        .unwrap();
+
+        for _ in 0..5 {
+            transport.send(item());
+        }
+        let dropped = transport.dropped_items();
+        assert!(dropped.overflow >= 3);
+        assert_eq!(dropped.rate_limited, 0);
+        assert!(!transport.shutdown(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_dropped_items_report() {
+        let configuration = Configuration {
+            access_token: Some("abc123".to_owned()),
+            ..Configuration::default()
+        };
+
+        let item = dropped_items_report(&configuration, 7).unwrap();
+        assert_eq!(item.data.level, Some(Level::Warning));
+        match item.data.body {
+            Body::MessageBody { message, .. } => assert_eq!(
+                message.body,
+                "Dropped 7 items because the transport queue was full"
+            ),
+            _ => panic!("expected a message body"),
+        }
+        assert!(dropped_items_report(&Configuration::default(), 7).is_none());
+    }
+
+    #[test]
+    fn test_stats() {
+        let server = StandIn::start(vec![stand_in::error(503, "busy"), stand_in::ok("1")]);
+        let transport = transport(&server, 3);
+
+        futures::executor::block_on(transport.send_async(item())).unwrap();
+        let stats = transport.stats();
+        assert_eq!(stats.queued, 0);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.sent, 1);
+        assert_eq!(stats.failed, 0);
+        assert_eq!(stats.retried, 1);
+        assert!(stats.last_success.is_some());
+        assert!(stats.latency_p50_ms.is_some());
+    }
+}
@@ -0,0 +1,401 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::rate_limit::RateLimiter;
+use super::stats::StatsRecorder;
+use super::{DroppedItems, Pipeline, SendError, SendFuture, Sent, Transport, TransportStats};
+use crate::configuration::Configuration;
+use crate::types::Item;
+
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 5;
+
+/// Where the lines end up.
+#[derive(Debug)]
+enum Sink {
+    File(RotatingFile),
+    Stdout,
+    Stderr,
+}
+
+impl Sink {
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        match self {
+            Sink::File(file) => file.write_line(line),
+            Sink::Stdout => io::stdout().lock().write_all(line),
+            Sink::Stderr => io::stderr().lock().write_all(line),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::File(file) => file.file.flush(),
+            Sink::Stdout => io::stdout().flush(),
+            Sink::Stderr => io::stderr().flush(),
+        }
+    }
+}
+
+/// A file that is moved aside to `<path>.1`, `<path>.2`, ... once it would
+/// grow past `max_size`, keeping at most `max_files` old files.
+#[derive(Debug)]
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            file,
+            size,
+            max_size: DEFAULT_MAX_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let len = line.len() as u64;
+        if self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(from, self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+}
+
+/// What the file and stdout transports have in common: items go through the
+/// same pipeline and rate limiting as with `HttpTransport` and are then
+/// written out as one JSON line each.
+#[derive(Debug)]
+struct Writer {
+    configuration: Configuration,
+    pipeline: Pipeline,
+    rate_limiter: RateLimiter,
+    stats: StatsRecorder,
+    sink: Mutex<Sink>,
+}
+
+impl Writer {
+    fn new(configuration: Configuration, sink: Sink) -> Self {
+        Writer {
+            pipeline: Pipeline::new(&configuration),
+            rate_limiter: RateLimiter::new(&configuration),
+            stats: StatsRecorder::default(),
+            sink: Mutex::new(sink),
+            configuration,
+        }
+    }
+
+    fn write(&self, mut item: Item) -> Result<Sent, SendError> {
+        if !self.pipeline.process(&mut item) {
+            return Err(SendError::Ignored);
+        }
+        if !self.rate_limiter.allow(&item.access_token) {
+            return Err(SendError::RateLimited);
+        }
+        self.stats.started();
+        let result = self.write_line(&item).map(|_| Sent {
+            id: None,
+            uuid: item.data.uuid.clone(),
+        });
+        self.stats.finished(&result);
+        if let Err(err) = &result {
+            error!("Failed to write item: {}", err);
+        }
+        result
+    }
+
+    fn write_line(&self, item: &Item) -> Result<(), SendError> {
+        let mut line =
+            serde_json::to_vec(item).map_err(|err| SendError::Request(err.to_string()))?;
+        line.push(b'\n');
+        self.sink
+            .lock()
+            .unwrap()
+            .write_line(&line)
+            .map_err(|err| SendError::Io(err.to_string()))
+    }
+
+    fn stats(&self) -> TransportStats {
+        self.stats.snapshot(DroppedItems {
+            overflow: 0,
+            rate_limited: self.rate_limiter.dropped(),
+            sampled: self.pipeline.sampled(),
+        })
+    }
+
+    fn flush(&self) -> bool {
+        self.sink.lock().unwrap().flush().is_ok()
+    }
+}
+
+/// A transport that appends each item as a JSON line to a file instead of
+/// sending it, for air-gapped environments. The file can be uploaded later
+/// with `rollbar-rust replay`.
+#[derive(Debug)]
+pub struct FileTransport {
+    writer: Writer,
+}
+
+impl FileTransport {
+    /// Append to the file at `path`, creating it if needed. The file is
+    /// rotated at 10 MiB and five rotated files are kept by default.
+    pub fn new<P: AsRef<Path>>(configuration: Configuration, path: P) -> io::Result<Self> {
+        let file = RotatingFile::open(path.as_ref().to_owned())?;
+        Ok(FileTransport {
+            writer: Writer::new(configuration, Sink::File(file)),
+        })
+    }
+
+    /// Rotate the file once it would grow past `max_size` bytes.
+    pub fn max_size(self, max_size: u64) -> Self {
+        self.with_file(|file| file.max_size = max_size)
+    }
+
+    /// How many rotated files to keep. With zero the file is truncated
+    /// instead of rotated.
+    pub fn max_files(self, max_files: usize) -> Self {
+        self.with_file(|file| file.max_files = max_files)
+    }
+
+    fn with_file<F: FnOnce(&mut RotatingFile)>(self, f: F) -> Self {
+        if let Sink::File(file) = &mut *self.writer.sink.lock().unwrap() {
+            f(file);
+        }
+        self
+    }
+
+    /// Read back the items in a file written by a `FileTransport`.
+    pub fn read_items<P: AsRef<Path>>(path: P) -> io::Result<Vec<Item>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut items = Vec::new();
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let item = serde_json::from_str(&line).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", n + 1, err),
+                )
+            })?;
+            items.push(item);
+        }
+        Ok(items)
+    }
+}
+
+impl Transport for FileTransport {
+    fn send(&self, item: Item) {
+        self.writer.write(item).ok();
+    }
+
+    fn send_async(&self, item: Item) -> SendFuture {
+        SendFuture::ready(self.writer.write(item))
+    }
+
+    fn config(&self) -> &Configuration {
+        &self.writer.configuration
+    }
+
+    fn stats(&self) -> TransportStats {
+        self.writer.stats()
+    }
+
+    fn flush(&self, _timeout: Duration) -> bool {
+        self.writer.flush()
+    }
+
+    fn shutdown(&self, _timeout: Duration) -> bool {
+        self.writer.flush()
+    }
+}
+
+/// A transport that prints each item as a JSON line to stdout, or stderr,
+/// instead of sending it. Handy for debugging and for piping items into
+/// other tools.
+#[derive(Debug)]
+pub struct StdoutTransport {
+    writer: Writer,
+}
+
+impl StdoutTransport {
+    pub fn new(configuration: Configuration) -> Self {
+        StdoutTransport {
+            writer: Writer::new(configuration, Sink::Stdout),
+        }
+    }
+
+    pub fn stderr(configuration: Configuration) -> Self {
+        StdoutTransport {
+            writer: Writer::new(configuration, Sink::Stderr),
+        }
+    }
+}
+
+impl Transport for StdoutTransport {
+    fn send(&self, item: Item) {
+        self.writer.write(item).ok();
+    }
+
+    fn send_async(&self, item: Item) -> SendFuture {
+        SendFuture::ready(self.writer.write(item))
+    }
+
+    fn config(&self) -> &Configuration {
+        &self.writer.configuration
+    }
+
+    fn stats(&self) -> TransportStats {
+        self.writer.stats()
+    }
+
+    fn flush(&self, _timeout: Duration) -> bool {
+        self.writer.flush()
+    }
+
+    fn shutdown(&self, _timeout: Duration) -> bool {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Body, Data, Message};
+
+    fn configuration() -> Configuration {
+        Configuration {
+            access_token: Some("abc123".to_owned()),
+            ..Configuration::default()
+        }
+    }
+
+    fn item(body: &str) -> Item {
+        let message = Message::builder().body(body).build();
+        let data = Data::builder()
+            .body(Body::builder().message(message).build())
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    fn message(item: &Item) -> &str {
+        match &item.data.body {
+            Body::MessageBody { message, .. } => &message.body,
+            _ => panic!("expected a message body"),
+        }
+    }
+
+    #[test]
+    fn test_writes_items_as_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("items.ndjson");
+        let transport = FileTransport::new(configuration(), &path).unwrap();
+
+        transport.send(item("first"));
+        let mut second = item("second");
+        second.data.custom = Some(
+            [("password".to_owned(), "hunter2".into())]
+                .into_iter()
+                .collect(),
+        );
+        transport.send(second);
+        assert!(transport.shutdown(Duration::from_secs(1)));
+
+        let items = FileTransport::read_items(&path).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(message(&items[0]), "first");
+        assert_eq!(message(&items[1]), "second");
+        assert_ne!(
+            items[1].data.custom.as_ref().unwrap()["password"],
+            "hunter2"
+        );
+        assert_eq!(transport.stats().sent, 2);
+    }
+
+    #[test]
+    fn test_rotates_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("items.ndjson");
+        let line_size = serde_json::to_vec(&item("0")).unwrap().len() as u64 + 1;
+        let transport = FileTransport::new(configuration(), &path)
+            .unwrap()
+            .max_size(2 * line_size)
+            .max_files(2);
+
+        for n in 0..7 {
+            transport.send(item(&n.to_string()));
+        }
+
+        let read = |name: &str| -> Vec<String> {
+            FileTransport::read_items(dir.path().join(name))
+                .unwrap()
+                .iter()
+                .map(|item| message(item).to_owned())
+                .collect()
+        };
+        assert_eq!(read("items.ndjson"), vec!["6"]);
+        assert_eq!(read("items.ndjson.1"), vec!["4", "5"]);
+        assert_eq!(read("items.ndjson.2"), vec!["2", "3"]);
+        assert!(!dir.path().join("items.ndjson.3").exists());
+    }
+
+    #[test]
+    fn test_honors_rate_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("items.ndjson");
+        let transport = FileTransport::new(
+            Configuration {
+                items_per_minute: 1,
+                ..configuration()
+            },
+            &path,
+        )
+        .unwrap();
+
+        transport.send(item("first"));
+        let result = futures::executor::block_on(transport.send_async(item("second")));
+
+        assert_eq!(result, Err(SendError::RateLimited));
+        assert_eq!(FileTransport::read_items(&path).unwrap().len(), 1);
+        assert_eq!(transport.stats().dropped.rate_limited, 1);
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::configuration::{Configuration, OverflowPolicy};
+use crate::types::Level;
+
+/// Anything that can be ranked when the queue has to pick an item to drop.
+pub(crate) trait Prioritized {
+    fn level(&self) -> Level;
+}
+
+#[derive(Debug)]
+struct State<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+/// A bounded queue between the callers of `send` and the sender task that
+/// applies the configured `OverflowPolicy` when it is full.
+#[derive(Debug)]
+pub(crate) struct Queue<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    timeout: Duration,
+    state: Mutex<State<T>>,
+    space: Condvar,
+    ready: Notify,
+    dropped: AtomicU64,
+}
+
+impl<T: Prioritized> Queue<T> {
+    pub(crate) fn new(configuration: &Configuration) -> Self {
+        Queue {
+            capacity: configuration.queue_depth.max(1),
+            policy: configuration.overflow_policy,
+            timeout: Duration::from_millis(configuration.overflow_timeout_ms),
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                closed: false,
+            }),
+            space: Condvar::new(),
+            ready: Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue `item`, returning whichever item had to be dropped to make room.
+    pub(crate) fn push(&self, item: T) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        if self.policy == OverflowPolicy::Block {
+            state = self
+                .space
+                .wait_timeout_while(state, self.timeout, |s| {
+                    s.items.len() >= self.capacity && !s.closed
+                })
+                .unwrap()
+                .0;
+        }
+        if state.closed {
+            return self.drop_item(item);
+        }
+
+        let mut evicted = None;
+        if state.items.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropNewest | OverflowPolicy::Block => {
+                    return self.drop_item(item);
+                }
+                OverflowPolicy::DropOldest => evicted = state.items.pop_front(),
+                OverflowPolicy::DropLowestLevel => {
+                    let lowest = state
+                        .items
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, queued)| queued.level())
+                        .filter(|(_, queued)| queued.level() < item.level())
+                        .map(|(index, _)| index);
+                    match lowest {
+                        Some(index) => evicted = state.items.remove(index),
+                        None => return self.drop_item(item),
+                    }
+                }
+            }
+        }
+        state.items.push_back(item);
+        self.ready.notify_one();
+        evicted.and_then(|item| self.drop_item(item))
+    }
+
+    /// Wait for the next item. Returns `None` once the queue is closed and
+    /// empty.
+    pub(crate) async fn pop(&self) -> Option<T> {
+        loop {
+            let ready = self.ready.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(item) = state.items.pop_front() {
+                    self.space.notify_one();
+                    return Some(item);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            ready.await;
+        }
+    }
+
+    /// Stop accepting items. Items already queued can still be popped.
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_one();
+        self.space.notify_all();
+    }
+
+    /// The number of items dropped because the queue was full or closed.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn drop_item(&self, item: T) -> Option<T> {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    impl Prioritized for (Level, u32) {
+        fn level(&self) -> Level {
+            self.0.clone()
+        }
+    }
+
+    fn queue(policy: OverflowPolicy) -> Queue<(Level, u32)> {
+        Queue::new(&Configuration {
+            queue_depth: 2,
+            overflow_policy: policy,
+            overflow_timeout_ms: 10,
+            ..Configuration::default()
+        })
+    }
+
+    fn drain(queue: &Queue<(Level, u32)>) -> Vec<u32> {
+        queue.close();
+        let mut items = Vec::new();
+        while let Some((_, n)) = futures::executor::block_on(queue.pop()) {
+            items.push(n);
+        }
+        items
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let queue = queue(OverflowPolicy::DropNewest);
+
+        assert_eq!(queue.push((Level::Error, 1)), None);
+        assert_eq!(queue.push((Level::Error, 2)), None);
+        assert_eq!(queue.push((Level::Error, 3)), Some((Level::Error, 3)));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(drain(&queue), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let queue = queue(OverflowPolicy::DropOldest);
+
+        queue.push((Level::Error, 1));
+        queue.push((Level::Error, 2));
+        assert_eq!(queue.push((Level::Error, 3)), Some((Level::Error, 1)));
+        assert_eq!(drain(&queue), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_drop_lowest_level() {
+        let queue = queue(OverflowPolicy::DropLowestLevel);
+
+        queue.push((Level::Info, 1));
+        queue.push((Level::Error, 2));
+        assert_eq!(queue.push((Level::Debug, 3)), Some((Level::Debug, 3)));
+        assert_eq!(queue.push((Level::Critical, 4)), Some((Level::Info, 1)));
+        assert_eq!(drain(&queue), vec![2, 4]);
+        assert_eq!(queue.dropped(), 2);
+    }
+
+    #[test]
+    fn test_block_gives_up_after_timeout() {
+        let queue = queue(OverflowPolicy::Block);
+        queue.push((Level::Error, 1));
+        queue.push((Level::Error, 2));
+
+        assert_eq!(queue.push((Level::Error, 3)), Some((Level::Error, 3)));
+        assert_eq!(drain(&queue), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_block_waits_for_space() {
+        let queue = Arc::new(Queue::new(&Configuration {
+            queue_depth: 1,
+            overflow_policy: OverflowPolicy::Block,
+            overflow_timeout_ms: 5000,
+            ..Configuration::default()
+        }));
+        queue.push((Level::Error, 1));
+
+        let consumer = queue.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            futures::executor::block_on(consumer.pop())
+        });
+
+        assert_eq!(queue.push((Level::Error, 2)), None);
+        assert_eq!(handle.join().unwrap(), Some((Level::Error, 1)));
+        assert_eq!(drain(&queue), vec![2]);
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::HeaderMap;
+
+use super::retry::retry_after;
+use crate::configuration::Configuration;
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// How long to hold off when the API rate limits us without saying for how
+/// long.
+const DEFAULT_BLOCK: Duration = Duration::from_secs(60);
+
+const REMAINING_HEADER: &str = "X-Rate-Limit-Remaining";
+const RESET_HEADER: &str = "X-Rate-Limit-Reset";
+
+#[derive(Debug)]
+struct Bucket {
+    window_start: Instant,
+    count: u32,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct State {
+    buckets: HashMap<String, Bucket>,
+    unreported: u64,
+    last_report: Instant,
+}
+
+impl State {
+    fn bucket(&mut self, access_token: &str, now: Instant) -> &mut Bucket {
+        self.buckets
+            .entry(access_token.to_owned())
+            .or_insert_with(|| Bucket {
+                window_start: now,
+                count: 0,
+                blocked_until: None,
+            })
+    }
+}
+
+/// Per access token rate limiting of outgoing items.
+///
+/// Items beyond `items_per_minute` in a one minute window are dropped. Once
+/// the API has told us a token is over its limit, the token is blocked until
+/// the limit resets; the transport spools or drops its items in the meantime.
+/// Dropped items are counted and the count is logged at most once a minute,
+/// by `report_drops`.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    items_per_minute: u32,
+    state: Mutex<State>,
+    dropped: AtomicU64,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(configuration: &Configuration) -> Self {
+        RateLimiter {
+            items_per_minute: configuration.items_per_minute,
+            state: Mutex::new(State {
+                buckets: HashMap::new(),
+                unreported: 0,
+                last_report: Instant::now(),
+            }),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether an item for `access_token` fits in the `items_per_minute`
+    /// budget. Counts the item against the budget when it does.
+    pub(crate) fn allow(&self, access_token: &str) -> bool {
+        self.allow_at(access_token, Instant::now())
+    }
+
+    fn allow_at(&self, access_token: &str, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let bucket = state.bucket(access_token, now);
+        if now.duration_since(bucket.window_start) >= WINDOW {
+            bucket.window_start = now;
+            bucket.count = 0;
+        }
+        let allowed = self.items_per_minute == 0 || bucket.count < self.items_per_minute;
+
+        if allowed {
+            bucket.count += 1;
+        } else {
+            self.record_drop(&mut state, now);
+        }
+        allowed
+    }
+
+    /// Whether the API has asked us to stop sending items for `access_token`.
+    pub(crate) fn is_blocked(&self, access_token: &str) -> bool {
+        self.is_blocked_at(access_token, Instant::now())
+    }
+
+    fn is_blocked_at(&self, access_token: &str, now: Instant) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .buckets
+            .get(access_token)
+            .and_then(|bucket| bucket.blocked_until)
+            .is_some_and(|until| now < until)
+    }
+
+    /// Count an item that was dropped because its token is blocked.
+    pub(crate) fn drop_blocked(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.record_drop(&mut state, Instant::now());
+    }
+
+    /// Log the drops not logged yet, if a minute has passed since the last
+    /// time or `force` is set.
+    pub(crate) fn report_drops(&self, force: bool) {
+        let mut state = self.state.lock().unwrap();
+        self.report_at(&mut state, Instant::now(), force);
+    }
+
+    /// Record the rate limit state reported by the API in response to an item.
+    pub(crate) fn update(&self, access_token: &str, status: u16, headers: &HeaderMap) {
+        self.update_at(access_token, status, headers, Instant::now());
+    }
+
+    fn update_at(&self, access_token: &str, status: u16, headers: &HeaderMap, now: Instant) {
+        let remaining = header(headers, REMAINING_HEADER);
+        if status != 429 && remaining != Some(0) {
+            return;
+        }
+        let block = header(headers, RESET_HEADER)
+            .map(|reset| {
+                let reset = UNIX_EPOCH + Duration::from_secs(reset);
+                reset
+                    .duration_since(SystemTime::now())
+                    .unwrap_or(Duration::ZERO)
+            })
+            .or_else(|| retry_after(headers))
+            .unwrap_or(DEFAULT_BLOCK);
+        debug!("rate limited by the API for {:?}", block);
+
+        let mut state = self.state.lock().unwrap();
+        state.bucket(access_token, now).blocked_until = Some(now + block);
+    }
+
+    fn record_drop(&self, state: &mut State, now: Instant) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        state.unreported += 1;
+        self.report_at(state, now, false);
+    }
+
+    fn report_at(&self, state: &mut State, now: Instant, force: bool) {
+        if state.unreported > 0 && (force || now.duration_since(state.last_report) >= WINDOW) {
+            warn!(
+                "dropped {} items due to rate limiting in the last {:?} ({} in total)",
+                state.unreported,
+                now.duration_since(state.last_report),
+                self.dropped()
+            );
+            state.unreported = 0;
+            state.last_report = now;
+        }
+    }
+
+    /// The total number of items dropped so far.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn limiter(items_per_minute: u32) -> RateLimiter {
+        RateLimiter::new(&Configuration {
+            items_per_minute,
+            ..Configuration::default()
+        })
+    }
+
+    #[test]
+    fn test_limits_items_per_minute_per_token() {
+        let limiter = limiter(2);
+        let now = Instant::now();
+
+        assert!(limiter.allow_at("a", now));
+        assert!(limiter.allow_at("a", now));
+        assert!(!limiter.allow_at("a", now));
+        assert!(limiter.allow_at("b", now));
+        assert!(limiter.allow_at("a", now + WINDOW));
+        assert_eq!(limiter.dropped(), 1);
+    }
+
+    #[test]
+    fn test_report_drops_flushes_the_count() {
+        let limiter = limiter(1);
+        let now = Instant::now();
+        limiter.allow_at("a", now);
+        limiter.allow_at("a", now);
+        assert_eq!(limiter.state.lock().unwrap().unreported, 1);
+
+        limiter.report_drops(false);
+        assert_eq!(limiter.state.lock().unwrap().unreported, 1);
+        limiter.report_drops(true);
+        assert_eq!(limiter.state.lock().unwrap().unreported, 0);
+    }
+
+    #[test]
+    fn test_zero_is_unlimited() {
+        let limiter = limiter(0);
+        let now = Instant::now();
+
+        assert!((0..1000).all(|_| limiter.allow_at("a", now)));
+    }
+
+    #[test]
+    fn test_blocks_on_server_rate_limit() {
+        let limiter = limiter(0);
+        let now = Instant::now();
+        let mut headers = HeaderMap::new();
+        headers.insert(REMAINING_HEADER, HeaderValue::from_static("5"));
+
+        limiter.update_at("a", 200, &headers, now);
+        assert!(!limiter.is_blocked_at("a", now));
+
+        let reset = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + WINDOW;
+        headers.insert(REMAINING_HEADER, HeaderValue::from_static("0"));
+        headers.insert(RESET_HEADER, HeaderValue::from(reset.as_secs()));
+        limiter.update_at("a", 200, &headers, now);
+
+        assert!(limiter.is_blocked_at("a", now));
+        assert!(!limiter.is_blocked_at("b", now));
+        assert!(!limiter.is_blocked_at("a", now + WINDOW + Duration::from_secs(1)));
+        assert!(limiter.allow_at("a", now));
+    }
+
+    #[test]
+    fn test_too_many_requests_without_headers() {
+        let limiter = limiter(0);
+        let now = Instant::now();
+
+        limiter.update_at("a", 429, &HeaderMap::new(), now);
+
+        assert!(limiter.is_blocked_at("a", now + DEFAULT_BLOCK / 2));
+        assert!(!limiter.is_blocked_at("a", now + DEFAULT_BLOCK));
+    }
+
+    #[test]
+    fn test_too_many_requests_with_retry_after() {
+        let limiter = limiter(0);
+        let now = Instant::now();
+        let mut headers = HeaderMap::new();
+        headers.insert("Retry-After", HeaderValue::from_static("0"));
+
+        limiter.update_at("a", 429, &headers, now);
+
+        assert!(!limiter.is_blocked_at("a", now));
+    }
+}
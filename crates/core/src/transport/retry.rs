@@ -0,0 +1,113 @@
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+use crate::configuration::Configuration;
+
+/// Exponential backoff with full jitter for transient send failures.
+#[derive(Debug, Clone)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub(crate) fn new(configuration: &Configuration) -> Self {
+        RetryPolicy {
+            max_retries: configuration.max_retries,
+            base_delay: Duration::from_millis(configuration.retry_base_delay_ms),
+            max_delay: Duration::from_millis(configuration.retry_max_delay_ms),
+        }
+    }
+
+    /// The delay before retry number `attempt` (starting at zero): a random
+    /// duration between half and all of `base_delay * 2^attempt`, capped at
+    /// `max_delay`.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exp / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+
+    /// The delay before retry number `attempt`: what the API asked for in
+    /// `Retry-After`, if anything, but never more than `max_delay`.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(retry_after) => retry_after.min(self.max_delay),
+            None => self.backoff(attempt),
+        }
+    }
+}
+
+/// Parse a `Retry-After` header given either in seconds or as an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_retry_after_is_capped() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+        };
+
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(3600))),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        assert!(policy.delay(0, None) <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+
+        for attempt in 0..10 {
+            let delay = policy.backoff(attempt);
+            let cap = (100 * 2u64.pow(attempt)).min(1000);
+            assert!(delay >= Duration::from_millis(cap / 2));
+            assert!(delay <= Duration::from_millis(cap));
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+}
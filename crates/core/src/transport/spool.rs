@@ -0,0 +1,454 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::types::Item;
+
+/// Upper bound on the size of a single segment file.
+const SEGMENT_SIZE: u64 = 1024 * 1024;
+const EXTENSION: &str = "spool";
+/// Acknowledged offsets of the segment with the same id, one per line.
+const ACKS_EXTENSION: &str = "acks";
+
+/// The location of an item in the spool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Record {
+    segment: u64,
+    offset: u64,
+}
+
+#[derive(Debug, Default)]
+struct Segment {
+    size: u64,
+    records: usize,
+    acked: HashSet<u64>,
+    in_flight: HashSet<u64>,
+}
+
+#[derive(Debug)]
+struct State {
+    segments: BTreeMap<u64, Segment>,
+    current: Option<(u64, File)>,
+    /// Whether the current segment has writes that are not synced yet.
+    dirty: bool,
+    next_segment: u64,
+    total_size: u64,
+}
+
+/// An on-disk queue of items waiting to be delivered.
+///
+/// Items are appended as JSON lines to segment files in `dir`, and synced to
+/// disk by the sender with `sync` rather than on the caller's thread.
+/// Acknowledgements are recorded next to each segment, and a segment is
+/// deleted once every item in it has been acknowledged. When the spool would
+/// grow past `max_size` the oldest segments are discarded. Delivery is at
+/// least once: an item whose acknowledgement was lost is sent again after a
+/// restart.
+#[derive(Debug)]
+pub(crate) struct Spool {
+    dir: PathBuf,
+    max_size: u64,
+    segment_size: u64,
+    state: Mutex<State>,
+}
+
+impl Spool {
+    /// Open the spool in `dir`, picking up any segments left by a previous
+    /// process.
+    pub(crate) fn open<P: AsRef<Path>>(dir: P, max_size: u64) -> io::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(&dir)?;
+
+        let mut segments = BTreeMap::new();
+        let mut total_size = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let id = match path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                Some(id) => id,
+                None => continue,
+            };
+            let size = fs::metadata(&path)?.len();
+            let records = BufReader::new(File::open(&path)?).lines().count();
+            let acked = read_acks(&path.with_extension(ACKS_EXTENSION))?;
+            total_size += size;
+            segments.insert(
+                id,
+                Segment {
+                    size,
+                    records,
+                    acked,
+                    ..Segment::default()
+                },
+            );
+        }
+        let next_segment = segments.keys().next_back().map_or(0, |id| id + 1);
+
+        let spool = Spool {
+            dir,
+            max_size,
+            segment_size: SEGMENT_SIZE.min(max_size / 4).max(1),
+            state: Mutex::new(State {
+                segments,
+                current: None,
+                dirty: false,
+                next_segment,
+                total_size,
+            }),
+        };
+        {
+            let mut state = spool.state.lock().unwrap();
+            let done: Vec<u64> = state
+                .segments
+                .iter()
+                .filter(|(_, segment)| segment.acked.len() >= segment.records)
+                .map(|(&id, _)| id)
+                .collect();
+            for id in done {
+                spool.remove(&mut state, id);
+            }
+        }
+        spool.remove_orphaned_acks()?;
+        Ok(spool)
+    }
+
+    /// Append `item`. It is only durable once `sync` has run. The record is
+    /// considered in flight until it is either acknowledged or released.
+    pub(crate) fn append(&self, item: &Item) -> io::Result<Record> {
+        let mut line = serde_json::to_vec(item)?;
+        line.push(b'\n');
+        let len = line.len() as u64;
+        if len > self.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "item is larger than the spool",
+            ));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let rotate = match &state.current {
+            Some((id, _)) => state.segments[id].size + len > self.segment_size,
+            None => true,
+        };
+        if rotate {
+            self.rotate(&mut state)?;
+        }
+        while state.total_size + len > self.max_size {
+            self.evict_oldest(&mut state)?;
+        }
+
+        let (id, file) = state.current.as_mut().unwrap();
+        let id = *id;
+        file.write_all(&line)?;
+
+        state.dirty = true;
+        state.total_size += len;
+        let segment = state.segments.get_mut(&id).unwrap();
+        let record = Record {
+            segment: id,
+            offset: segment.records as u64,
+        };
+        segment.size += len;
+        segment.records += 1;
+        segment.in_flight.insert(record.offset);
+        Ok(record)
+    }
+
+    /// Flush appended items to disk.
+    pub(crate) fn sync(&self) -> io::Result<()> {
+        let file = {
+            let mut state = self.state.lock().unwrap();
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            match &state.current {
+                Some((_, file)) => file.try_clone()?,
+                None => return Ok(()),
+            }
+        };
+        // Sync without holding the lock, so appends are not held up.
+        file.sync_data()
+    }
+
+    /// Mark a record as delivered (or undeliverable), deleting its segment
+    /// once nothing in it is left to send.
+    pub(crate) fn ack(&self, record: Record) {
+        let mut state = self.state.lock().unwrap();
+        let done = match state.segments.get_mut(&record.segment) {
+            Some(segment) => {
+                segment.in_flight.remove(&record.offset);
+                segment.acked.insert(record.offset);
+                segment.acked.len() == segment.records
+            }
+            None => return,
+        };
+        if done {
+            if state.current.as_ref().map(|(id, _)| *id) == Some(record.segment) {
+                state.current = None;
+                state.dirty = false;
+            }
+            self.remove(&mut state, record.segment);
+        } else if let Err(err) = self.write_ack(record) {
+            warn!("failed to record spool acknowledgement: {}", err);
+        }
+    }
+
+    /// Give a record back to the spool so it is picked up by the next drain.
+    pub(crate) fn release(&self, record: Record) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(segment) = state.segments.get_mut(&record.segment) {
+            segment.in_flight.remove(&record.offset);
+        }
+    }
+
+    /// Whether any records are waiting that nobody is currently sending.
+    pub(crate) fn has_backlog(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state
+            .segments
+            .values()
+            .any(|s| s.acked.len() + s.in_flight.len() < s.records)
+    }
+
+    /// Load the records that are neither acknowledged nor in flight, oldest
+    /// first, marking them as in flight.
+    pub(crate) fn drain(&self) -> io::Result<Vec<(Record, Item)>> {
+        let mut state = self.state.lock().unwrap();
+        let mut pending = Vec::new();
+        for (&id, segment) in state.segments.iter_mut() {
+            if segment.acked.len() + segment.in_flight.len() == segment.records {
+                continue;
+            }
+            let file = File::open(self.path(id))?;
+            for (offset, line) in BufReader::new(file).lines().enumerate() {
+                let offset = offset as u64;
+                let line = line?;
+                if segment.acked.contains(&offset) || segment.in_flight.contains(&offset) {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(item) => {
+                        segment.in_flight.insert(offset);
+                        pending.push((
+                            Record {
+                                segment: id,
+                                offset,
+                            },
+                            item,
+                        ));
+                    }
+                    Err(err) => {
+                        warn!("discarding corrupt spool record: {}", err);
+                        segment.acked.insert(offset);
+                    }
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    fn rotate(&self, state: &mut State) -> io::Result<()> {
+        if let Some((id, file)) = state.current.take() {
+            // `sync` only sees the current segment, so the outgoing one has
+            // to be synced before it is replaced.
+            if state.dirty {
+                file.sync_data()?;
+                state.dirty = false;
+            }
+            let segment = &state.segments[&id];
+            if segment.acked.len() == segment.records {
+                self.remove(state, id);
+            }
+        }
+        let id = state.next_segment;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(id))?;
+        state.next_segment += 1;
+        state.segments.insert(id, Segment::default());
+        state.current = Some((id, file));
+        Ok(())
+    }
+
+    fn evict_oldest(&self, state: &mut State) -> io::Result<()> {
+        let current = state.current.as_ref().map(|(id, _)| *id);
+        let oldest = state
+            .segments
+            .keys()
+            .copied()
+            .find(|&id| Some(id) != current);
+        match oldest {
+            Some(id) => {
+                let segment = &state.segments[&id];
+                warn!(
+                    "spool is full, discarding {} undelivered items",
+                    segment.records - segment.acked.len()
+                );
+                self.remove(state, id);
+                Ok(())
+            }
+            None => Err(io::Error::other("spool is full")),
+        }
+    }
+
+    fn remove(&self, state: &mut State, id: u64) {
+        if let Some(segment) = state.segments.remove(&id) {
+            state.total_size -= segment.size;
+            if let Err(err) = fs::remove_file(self.path(id)) {
+                warn!("failed to remove spool segment {}: {}", id, err);
+            }
+            let acks = self.path(id).with_extension(ACKS_EXTENSION);
+            match fs::remove_file(acks) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                    warn!("failed to remove spool acknowledgements {}: {}", id, err);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn write_ack(&self, record: Record) -> io::Result<()> {
+        let mut acks = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(record.segment).with_extension(ACKS_EXTENSION))?;
+        writeln!(acks, "{}", record.offset)
+    }
+
+    /// Remove acknowledgements left behind by segments that are gone.
+    fn remove_orphaned_acks(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(ACKS_EXTENSION)
+                && !path.with_extension(EXTENSION).exists()
+            {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:020}.{}", id, EXTENSION))
+    }
+}
+
+/// The offsets recorded in an acknowledgements file, if there is one.
+fn read_acks(path: &Path) -> io::Result<HashSet<u64>> {
+    match File::open(path) {
+        Ok(file) => Ok(BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| line.trim().parse().ok())
+            .collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Body, Data, Message};
+
+    fn item(body: &str) -> Item {
+        let message = Message::builder().body(body).build();
+        let data = Data::builder()
+            .body(Body::builder().message(message).build())
+            .build();
+        Item::builder().access_token("abc123").data(data).build()
+    }
+
+    fn message(item: &Item) -> &str {
+        match &item.data.body {
+            Body::MessageBody { message, .. } => &message.body,
+            _ => panic!("expected a message body"),
+        }
+    }
+
+    fn segments(dir: &Path) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn test_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let spool = Spool::open(dir.path(), 1024 * 1024).unwrap();
+            let first = spool.append(&item("first")).unwrap();
+            spool.append(&item("second")).unwrap();
+            spool.ack(first);
+        }
+
+        let spool = Spool::open(dir.path(), 1024 * 1024).unwrap();
+        assert!(spool.has_backlog());
+        let pending = spool.drain().unwrap();
+        let messages: Vec<_> = pending.iter().map(|(_, item)| message(item)).collect();
+        assert_eq!(messages, vec!["second"]);
+        assert!(!spool.has_backlog());
+
+        pending
+            .into_iter()
+            .for_each(|(record, _)| spool.ack(record));
+        assert_eq!(segments(dir.path()), 0);
+    }
+
+    #[test]
+    fn test_fully_acked_current_segment_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let spool = Spool::open(dir.path(), 1024 * 1024).unwrap();
+            let first = spool.append(&item("first")).unwrap();
+            spool.sync().unwrap();
+            spool.ack(first);
+            assert_eq!(segments(dir.path()), 0);
+
+            let second = spool.append(&item("second")).unwrap();
+            spool.ack(second);
+        }
+
+        let spool = Spool::open(dir.path(), 1024 * 1024).unwrap();
+        assert!(!spool.has_backlog());
+        assert!(spool.drain().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_released_records_are_drained() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(dir.path(), 1024 * 1024).unwrap();
+        let first = spool.append(&item("first")).unwrap();
+        let second = spool.append(&item("second")).unwrap();
+        assert!(!spool.has_backlog());
+
+        spool.ack(first);
+        spool.release(second);
+
+        let pending = spool.drain().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, second);
+        assert_eq!(message(&pending[0].1), "second");
+    }
+
+    #[test]
+    fn test_discards_oldest_segments_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = serde_json::to_vec(&item("0")).unwrap().len() as u64 + 1;
+        let spool = Spool::open(dir.path(), size * 8).unwrap();
+
+        for i in 0..20 {
+            let record = spool.append(&item(&i.to_string())).unwrap();
+            spool.release(record);
+        }
+
+        let pending = spool.drain().unwrap();
+        assert!(pending.len() <= 8);
+        assert_eq!(message(&pending.last().unwrap().1), "19");
+        assert!(spool.append(&item(&"x".repeat(10_000))).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! A minimal HTTP server standing in for the Rollbar API in tests.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A canned response: status, extra headers and body.
+pub(crate) type Reply = (u16, Vec<(&'static str, String)>, String);
+
+pub(crate) struct StandIn {
+    pub(crate) url: String,
+    requests: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl StandIn {
+    /// Serve `replies` in order, one per connection, repeating the last one
+    /// once they run out.
+    pub(crate) fn start(replies: Vec<Reply>) -> StandIn {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/1/item/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        thread::spawn(move || {
+            let mut replies = replies.into_iter().peekable();
+            let mut last = None;
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+                let request = read_request(&mut stream);
+                recorded.lock().unwrap().push(request);
+
+                let (status, headers, body) = match replies.next() {
+                    Some(reply) => {
+                        last = Some(reply.clone());
+                        reply
+                    }
+                    None => last.clone().unwrap_or((200, vec![], "{}".to_owned())),
+                };
+                let mut response = format!(
+                    "HTTP/1.1 {} Stand-In\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                stream.write_all(response.as_bytes()).ok();
+            }
+        });
+
+        StandIn { url, requests }
+    }
+
+    /// The bodies of every request received so far.
+    pub(crate) fn requests(&self) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().map(|(_, body)| body.clone()).collect()
+    }
+
+    /// The method and path of every request received so far, e.g.
+    /// `POST /api/1/item/`.
+    pub(crate) fn request_lines(&self) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().map(|(line, _)| line.clone()).collect()
+    }
+}
+
+pub(crate) fn ok(id: &str) -> Reply {
+    (
+        200,
+        vec![],
+        format!(
+            r#"{{"err":0,"result":{{"id":"{}","uuid":"{}-uuid"}}}}"#,
+            id, id
+        ),
+    )
+}
+
+pub(crate) fn error(status: u16, message: &str) -> Reply {
+    (
+        status,
+        vec![],
+        format!(r#"{{"err":1,"message":"{}"}}"#, message),
+    )
+}
+
+/// The method and path, and the body of a request.
+fn read_request<R: Read>(stream: R) -> (String, String) {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok();
+    let request_line = request_line.rsplit_once(' ').map_or("", |(line, _)| line);
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok();
+    (
+        request_line.to_owned(),
+        String::from_utf8_lossy(&body).into_owned(),
+    )
+}
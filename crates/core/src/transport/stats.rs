@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::{DroppedItems, SendError, TransportStats};
+use crate::telemetry::timestamp_ms;
+
+/// How many recent request latencies the percentiles are computed over.
+const LATENCY_SAMPLES: usize = 1000;
+
+#[derive(Debug, Default)]
+struct Recent {
+    last_error: Option<String>,
+    last_success: Option<u64>,
+    latencies: VecDeque<u64>,
+}
+
+/// Collects the counters behind `TransportStats`.
+#[derive(Debug, Default)]
+pub(crate) struct StatsRecorder {
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    retried: AtomicU64,
+    recent: Mutex<Recent>,
+}
+
+impl StatsRecorder {
+    pub(crate) fn queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn unqueued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// An item was taken off the queue and is about to be sent.
+    pub(crate) fn started(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retried(&self) {
+        self.retried.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how long a single request to the API took.
+    pub(crate) fn latency(&self, started_ms: u64) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.latencies.len() == LATENCY_SAMPLES {
+            recent.latencies.pop_front();
+        }
+        recent
+            .latencies
+            .push_back(timestamp_ms().saturating_sub(started_ms));
+    }
+
+    /// Record the final outcome of an item that was `started`.
+    pub(crate) fn finished<T>(&self, result: &Result<T, SendError>) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        match result {
+            Ok(_) => {
+                self.sent.fetch_add(1, Ordering::Relaxed);
+                self.recent.lock().unwrap().last_success = Some(timestamp_ms());
+            }
+            // Counted by the rate limiter as a dropped item.
+            Err(SendError::RateLimited) => {}
+            Err(err) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                self.recent.lock().unwrap().last_error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn snapshot(&self, dropped: DroppedItems) -> TransportStats {
+        let recent = self.recent.lock().unwrap();
+        let mut latencies: Vec<u64> = recent.latencies.iter().copied().collect();
+        latencies.sort_unstable();
+
+        TransportStats {
+            queued: self.queued.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            dropped,
+            last_error: recent.last_error.clone(),
+            last_success: recent.last_success,
+            latency_p50_ms: percentile(&latencies, 50),
+            latency_p99_ms: percentile(&latencies, 99),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u64], p: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100);
+    Some(sorted[rank.saturating_sub(1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<u64> = (1..=100).collect();
+
+        assert_eq!(percentile(&[], 50), None);
+        assert_eq!(percentile(&[7], 99), Some(7));
+        assert_eq!(percentile(&samples, 50), Some(50));
+        assert_eq!(percentile(&samples, 99), Some(99));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let stats = StatsRecorder::default();
+        stats.queued();
+        stats.queued();
+        stats.unqueued();
+        stats.started();
+        stats.finished::<()>(&Err(SendError::Timeout));
+        stats.started();
+        stats.finished(&Ok(()));
+        stats.started();
+
+        let snapshot = stats.snapshot(DroppedItems::default());
+        assert_eq!(snapshot.queued, 1);
+        assert_eq!(snapshot.in_flight, 1);
+        assert_eq!(snapshot.sent, 1);
+        assert_eq!(snapshot.failed, 1);
+        assert_eq!(snapshot.last_error.as_deref(), Some("request timed out"));
+        assert!(snapshot.last_success.is_some());
+    }
+}
@@ -1,17 +1,25 @@
+use super::stats::StatsRecorder;
+use super::{read_response, DroppedItems, Pipeline, SendError, SendFuture, Sent, TransportStats};
+use crate::errors::{self, ErrorKind};
+use crate::telemetry::timestamp_ms;
 use crate::{configuration::Configuration, types::Item};
 
 use futures::channel::{mpsc, oneshot};
 use reqwest::Client;
 use wasm_bindgen_futures::spawn_local;
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
 pub struct HttpTransport {
     configuration: Configuration,
+    pipeline: Pipeline,
     client: Client,
-    sender: Arc<Mutex<mpsc::Sender<Item>>>,
+    sender: Arc<Mutex<mpsc::Sender<Queued>>>,
     send_shutdown: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    stats: Arc<StatsRecorder>,
+    dropped: Arc<AtomicU64>,
 }
 
 use super::Transport;
@@ -20,21 +28,37 @@ use std::time::Duration;
 
 use futures::stream::StreamExt;
 
+/// An item on its way to the sender and where to report the outcome.
+type Queued = (Item, Option<oneshot::Sender<Result<Sent, SendError>>>);
+
 impl Transport for HttpTransport {
-    fn send(&self, item: Item) {
-        if let Ok(mut items) = self.sender.lock() {
-            log::info!("sending: {:?}", item);
+    fn send(&self, mut item: Item) {
+        if self.pipeline.process(&mut item) {
+            self.enqueue((item, None));
+        }
+    }
 
-            if let Err(error) = items.try_send(item) {
-                log::error!("error sending item: {}", error);
-            }
+    fn send_async(&self, mut item: Item) -> SendFuture {
+        if !self.pipeline.process(&mut item) {
+            return SendFuture::ready(Err(SendError::Ignored));
         }
+        let (reply, future) = SendFuture::channel();
+        self.enqueue((item, Some(reply)));
+        future
     }
 
     fn config(&self) -> &Configuration {
         &self.configuration
     }
 
+    fn stats(&self) -> TransportStats {
+        self.stats.snapshot(DroppedItems {
+            overflow: self.dropped.load(Ordering::Relaxed),
+            rate_limited: 0,
+            sampled: self.pipeline.sampled(),
+        })
+    }
+
     fn shutdown(&self, timeout: Duration) -> bool {
         self.send_shutdown
             .lock()
@@ -46,21 +70,25 @@ impl Transport for HttpTransport {
     }
 }
 
-use super::QUEUE_DEPTH;
-
 impl HttpTransport {
-    pub fn new(configuration: Configuration) -> Result<Self, reqwest::Error> {
-        let mut client = Client::builder().build()?;
+    pub fn new(configuration: Configuration) -> errors::Result<Self> {
+        configuration.validate()?;
+        let mut client = Client::builder()
+            .build()
+            .map_err(|err| ErrorKind::Transport(err.to_string()))?;
 
-        let (sender, receiver) = mpsc::channel::<Item>(QUEUE_DEPTH);
+        let (sender, receiver) = mpsc::channel::<Queued>(configuration.queue_depth);
 
         let (send_shutdown, receive_shutdown) = oneshot::channel();
 
         let mut transport = Self {
+            pipeline: Pipeline::new(&configuration),
             configuration,
             client,
             sender: Arc::new(Mutex::new(sender)),
             send_shutdown: Arc::new(Mutex::new(Some(send_shutdown))),
+            stats: Arc::new(StatsRecorder::default()),
+            dropped: Arc::new(AtomicU64::new(0)),
         };
 
         transport.run(receiver, receive_shutdown);
@@ -68,10 +96,23 @@ impl HttpTransport {
         Ok(transport)
     }
 
-    fn run(&mut self, mut items: mpsc::Receiver<Item>, mut shutdown: oneshot::Receiver<()>) {
+    fn enqueue(&self, queued: Queued) {
+        if let Ok(mut items) = self.sender.lock() {
+            log::info!("sending: {:?}", queued.0);
+
+            self.stats.queued();
+            if let Err(error) = items.try_send(queued) {
+                log::error!("error sending item: {}", error);
+                self.stats.unqueued();
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
 
+    fn run(&mut self, mut items: mpsc::Receiver<Queued>, mut shutdown: oneshot::Receiver<()>) {
         let client = self.client.clone();
         let conf = self.configuration.clone();
+        let stats = self.stats.clone();
 
         spawn_local(async move {
             loop {
@@ -80,11 +121,19 @@ impl HttpTransport {
                     item = items.next() => {
                         log::info!("-> {:?}", item);
 
-                        if let Some(item) = item {
-                            if let Err(error) = post(&client, &conf, &item).await {
-                                log::info!("error sending request: {:?}", error);
-                            } else {
-                                log::info!("sent request: {:?}", item);
+                        if let Some((item, reply)) = item {
+                            stats.unqueued();
+                            stats.started();
+                            let started = timestamp_ms();
+                            let result = post(&client, &conf, &item).await;
+                            stats.latency(started);
+                            stats.finished(&result);
+                            match &result {
+                                Err(error) => log::info!("error sending request: {}", error),
+                                Ok(sent) => log::info!("sent request: {:?}", sent),
+                            }
+                            if let Some(reply) = reply {
+                                reply.send(result).ok();
                             }
                         }
                     },
@@ -94,16 +143,18 @@ impl HttpTransport {
     }
 }
 
-async fn post(client: &Client, conf: &Configuration, item: &Item) -> Result<(), Option<reqwest::Error>> {
-    let access_token = conf.access_token.as_ref().map_or(Err(None), Ok)?;
+async fn post(client: &Client, conf: &Configuration, item: &Item) -> Result<Sent, SendError> {
+    let access_token = conf
+        .access_token
+        .as_ref()
+        .ok_or_else(|| SendError::Request("missing access token".to_owned()))?;
     let endpoint = &conf.endpoint;
 
-    client
+    let resp = client
         .post(endpoint.as_str())
         .json(&item)
         .header("X-Rollbar-Access-Token", access_token)
         .send()
-        .await
-        .map(|_| ())
-        .map_err(Some)
+        .await?;
+    read_response(resp, None).await
 }
@@ -0,0 +1,365 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::configuration::Configuration;
+use crate::constants;
+use crate::types::*;
+
+/// Frames kept at each end of a trace when dropping middle frames.
+const KEPT_FRAMES: usize = 10;
+/// Entries kept per collection when capping collection sizes.
+const KEPT_ENTRIES: usize = 10;
+/// Length of the message body once everything else has failed.
+const MIN_BODY_LEN: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Frames(usize),
+    Strings(usize),
+    Collections(usize),
+    Locals,
+    Telemetry,
+    MinBody,
+}
+
+const STEPS: &[Step] = &[
+    Step::Frames(KEPT_FRAMES),
+    Step::Strings(1024),
+    Step::Strings(256),
+    Step::Collections(KEPT_ENTRIES),
+    Step::Locals,
+    Step::Telemetry,
+    Step::MinBody,
+];
+
+impl Step {
+    fn name(&self) -> String {
+        match self {
+            Step::Frames(n) => format!("frames:{}", n),
+            Step::Strings(n) => format!("strings:{}", n),
+            Step::Collections(n) => format!("collections:{}", n),
+            Step::Locals => "locals".to_owned(),
+            Step::Telemetry => "telemetry".to_owned(),
+            Step::MinBody => "min_body".to_owned(),
+        }
+    }
+}
+
+/// Shrinks items whose serialized size exceeds `max_item_size`.
+///
+/// Progressively more destructive steps are applied until the item fits:
+/// dropping middle frames, trimming strings, capping collections, stripping
+/// locals, dropping telemetry and finally cutting the body down to a minimum.
+/// The applied steps and the original size are recorded under
+/// `notifier.diagnostic.truncation`.
+#[derive(Debug, Clone)]
+pub struct Truncator {
+    max_size: usize,
+}
+
+impl Truncator {
+    pub fn new(configuration: &Configuration) -> Self {
+        Truncator {
+            max_size: configuration.max_item_size,
+        }
+    }
+
+    /// Returns whether the item fits the budget afterwards.
+    pub fn truncate_item(&self, item: &mut Item) -> bool {
+        let original_size = serialized_size(item);
+        if original_size <= self.max_size {
+            return true;
+        }
+
+        let mut applied = Vec::new();
+        let mut size = original_size;
+        for step in STEPS {
+            apply(*step, &mut item.data);
+            applied.push(Value::from(step.name()));
+            size = serialized_size(item);
+            if size <= self.max_size {
+                break;
+            }
+        }
+        debug!(
+            "truncated item from {} to {} bytes: {:?}",
+            original_size, size, applied
+        );
+
+        let mut truncation = serde_json::Map::new();
+        truncation.insert("original_size".to_owned(), Value::from(original_size));
+        truncation.insert("steps".to_owned(), Value::Array(applied));
+        item.data
+            .notifier
+            .get_or_insert_with(|| constants::NOTIFIER.clone())
+            .diagnostic
+            .get_or_insert_with(HashMap::new)
+            .insert("truncation".to_owned(), Value::Object(truncation));
+
+        serialized_size(item) <= self.max_size
+    }
+}
+
+fn serialized_size(item: &Item) -> usize {
+    serde_json::to_vec(item).map(|v| v.len()).unwrap_or(0)
+}
+
+fn apply(step: Step, data: &mut Data) {
+    match step {
+        Step::Frames(keep) => traces(&mut data.body)
+            .into_iter()
+            .for_each(|t| drop_middle(&mut t.frames, keep)),
+        Step::Strings(len) => {
+            strings(data).into_iter().for_each(|s| truncate_str(s, len));
+            maps(data)
+                .into_iter()
+                .flat_map(|m| m.values_mut())
+                .for_each(|v| truncate_value_strings(v, len));
+        }
+        Step::Collections(len) => maps(data).into_iter().for_each(|m| {
+            cap_map(m, len);
+            m.values_mut().for_each(|v| cap_value(v, len));
+        }),
+        Step::Locals => {
+            for trace in traces(&mut data.body) {
+                for frame in &mut trace.frames {
+                    frame.locals = None;
+                    frame.context = None;
+                    frame.argspec = None;
+                }
+            }
+        }
+        Step::Telemetry => {
+            if let Some(telemetry) = data.body.telemetry_mut() {
+                *telemetry = None;
+            }
+        }
+        Step::MinBody => {
+            traces(&mut data.body)
+                .into_iter()
+                .for_each(|t| drop_middle(&mut t.frames, 1));
+            strings(data)
+                .into_iter()
+                .for_each(|s| truncate_str(s, MIN_BODY_LEN));
+            data.custom = None;
+            data.request = None;
+            if let Body::MessageBody { message, .. } = &mut data.body {
+                message.extra.clear();
+            }
+        }
+    }
+}
+
+fn traces(body: &mut Body) -> Vec<&mut Trace> {
+    match body {
+        Body::TraceBody { trace, .. } => vec![trace],
+        Body::TraceChainBody { trace_chain, .. } => trace_chain.iter_mut().collect(),
+        _ => vec![],
+    }
+}
+
+fn strings(data: &mut Data) -> Vec<&mut String> {
+    let mut strings = Vec::new();
+    match &mut data.body {
+        Body::MessageBody { message, .. } => strings.push(&mut message.body),
+        Body::CrashReportBody { crash_report, .. } => strings.push(&mut crash_report.raw),
+        Body::TraceBody { trace, .. } => trace_strings(trace, &mut strings),
+        Body::TraceChainBody { trace_chain, .. } => trace_chain
+            .iter_mut()
+            .for_each(|t| trace_strings(t, &mut strings)),
+        Body::Unset => {}
+    }
+    if let Some(body) = data.request.as_mut().and_then(|r| r.body.as_mut()) {
+        strings.push(body);
+    }
+    strings
+}
+
+fn trace_strings<'a>(trace: &'a mut Trace, strings: &mut Vec<&'a mut String>) {
+    strings.extend(trace.exception.message.as_mut());
+    strings.extend(trace.exception.description.as_mut());
+    strings.extend(
+        trace
+            .frames
+            .iter_mut()
+            .filter_map(|f| f.function_code_line.as_mut()),
+    );
+}
+
+fn maps(data: &mut Data) -> Vec<&mut HashMap<String, Value>> {
+    let mut maps = Vec::new();
+    maps.extend(data.custom.as_mut());
+    if let Some(request) = data.request.as_mut() {
+        maps.extend(request.headers.as_mut());
+        maps.extend(request.params.as_mut());
+        maps.extend(request.get.as_mut());
+        maps.extend(request.post.as_mut());
+    }
+    match &mut data.body {
+        Body::MessageBody {
+            message, telemetry, ..
+        } => {
+            maps.push(&mut message.extra);
+            maps.extend(telemetry.iter_mut().flatten().map(|t| &mut t.body));
+        }
+        Body::TraceBody {
+            trace, telemetry, ..
+        } => {
+            maps.extend(trace.frames.iter_mut().filter_map(|f| f.locals.as_mut()));
+            maps.extend(telemetry.iter_mut().flatten().map(|t| &mut t.body));
+        }
+        Body::TraceChainBody {
+            trace_chain,
+            telemetry,
+        } => {
+            maps.extend(
+                trace_chain
+                    .iter_mut()
+                    .flat_map(|t| t.frames.iter_mut())
+                    .filter_map(|f| f.locals.as_mut()),
+            );
+            maps.extend(telemetry.iter_mut().flatten().map(|t| &mut t.body));
+        }
+        Body::CrashReportBody { telemetry, .. } => {
+            maps.extend(telemetry.iter_mut().flatten().map(|t| &mut t.body));
+        }
+        Body::Unset => {}
+    }
+    maps
+}
+
+fn drop_middle<T>(items: &mut Vec<T>, keep: usize) {
+    if items.len() > 2 * keep {
+        let end = items.len() - keep;
+        items.drain(keep..end);
+    }
+}
+
+fn truncate_str(s: &mut String, len: usize) {
+    if s.len() > len {
+        let end = (0..=len)
+            .rev()
+            .find(|&i| s.is_char_boundary(i))
+            .unwrap_or(0);
+        s.truncate(end);
+        s.push_str("...");
+    }
+}
+
+fn truncate_value_strings(value: &mut Value, len: usize) {
+    match value {
+        Value::String(s) => truncate_str(s, len),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|v| truncate_value_strings(v, len)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|v| truncate_value_strings(v, len)),
+        _ => {}
+    }
+}
+
+fn cap_map(map: &mut HashMap<String, Value>, len: usize) {
+    if map.len() > len {
+        let mut keys: Vec<String> = map.keys().cloned().collect();
+        keys.sort();
+        for key in keys.into_iter().skip(len) {
+            map.remove(&key);
+        }
+    }
+}
+
+fn cap_value(value: &mut Value, len: usize) {
+    match value {
+        Value::Array(values) => {
+            values.truncate(len);
+            values.iter_mut().for_each(|v| cap_value(v, len));
+        }
+        Value::Object(map) => {
+            if map.len() > len {
+                let keys: Vec<String> = map.keys().skip(len).cloned().collect();
+                for key in keys {
+                    map.remove(&key);
+                }
+            }
+            map.values_mut().for_each(|v| cap_value(v, len));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truncator(max_item_size: usize) -> Truncator {
+        Truncator::new(&Configuration {
+            max_item_size,
+            ..Configuration::default()
+        })
+    }
+
+    fn trace_item(frames: usize) -> Item {
+        let frames = (0..frames)
+            .map(|i| {
+                Frame::builder()
+                    .filename("main.rs")
+                    .lineno(i as i32)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let trace = Trace::builder().frames(frames).build();
+        let body = Body::builder().trace(trace).build();
+        Item::builder()
+            .access_token("abc123")
+            .data(Data::builder().body(body).build())
+            .build()
+    }
+
+    #[test]
+    fn test_small_item_is_untouched() {
+        let mut item = trace_item(5);
+
+        assert!(truncator(1024).truncate_item(&mut item));
+        assert!(item.data.notifier.is_none());
+    }
+
+    #[test]
+    fn test_drops_middle_frames() {
+        let mut item = trace_item(500);
+
+        assert!(truncator(2048).truncate_item(&mut item));
+        match &item.data.body {
+            Body::TraceBody { trace, .. } => {
+                assert_eq!(trace.frames.len(), 2 * KEPT_FRAMES);
+                assert_eq!(trace.frames[0].lineno, Some(0));
+                assert_eq!(trace.frames[19].lineno, Some(499));
+            }
+            _ => panic!("expected a trace body"),
+        }
+        let diagnostic = item.data.notifier.unwrap().diagnostic.unwrap();
+        assert_eq!(diagnostic["truncation"]["steps"][0], "frames:10");
+    }
+
+    #[test]
+    fn test_trims_long_message() {
+        let message = Message::builder().body("x".repeat(100_000)).build();
+        let body = Body::builder().message(message).build();
+        let mut item = Item::builder()
+            .data(Data::builder().body(body).build())
+            .build();
+
+        assert!(truncator(2048).truncate_item(&mut item));
+        match &item.data.body {
+            Body::MessageBody { message, .. } => assert_eq!(message.body.len(), 1027),
+            _ => panic!("expected a message body"),
+        }
+    }
+
+    #[test]
+    fn test_truncate_str_on_char_boundary() {
+        let mut s = "héllo".to_owned();
+        truncate_str(&mut s, 2);
+        assert_eq!(s, "h...");
+    }
+}
@@ -175,6 +175,8 @@ pub struct Notifier {
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostic: Option<HashMap<String, Value>>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
@@ -1,6 +1,4 @@
 use crate::rollbar::Rollbar;
-use aho_corasick::{AcAutomaton, Automaton};
-use lazy_static::lazy_static;
 use rollbar_jvm::env::JvmTiEnv;
 use rollbar_jvm::errors::*;
 use rollbar_jvm::jni::JniEnv;
@@ -8,6 +6,30 @@ use rollbar_jvm::jvmti::{jlocation, jmethodID, jobject, jthread};
 use rollbar_rust::types::*;
 use std::sync::atomic::{AtomicPtr, Ordering};
 
+/// Exceptions the JVM routinely throws and catches, ignored unless the
+/// configuration sets `ignored_exception_classes` itself.
+pub const DEFAULT_IGNORED_EXCEPTION_CLASSES: &[&str] = &[
+    "com.sun.org.",
+    "javax.naming.",
+    "java.io.EOFException",
+    "java.io.FileNotFoundException",
+    "java.io.IOException",
+    "java.lang.ArrayIndexOutOfBoundsException",
+    "java.lang.ClassNotFoundException",
+    "java.lang.IllegalStateException",
+    "java.lang.InterruptedException",
+    "java.lang.NoSuchFieldError",
+    "java.lang.NoSuchFieldException",
+    "java.lang.NoSuchMethodException",
+    "java.net.MalformedURLException",
+    "java.net.SocketException",
+    "java.security.cert.CertificateParsingException",
+    "java.security.PrivilegedActionException",
+    "java.security.SignatureException",
+    "java.util.zip.ZipException",
+    "javax.crypto.BadPaddingException",
+];
+
 static LAST_EXCEPTION: AtomicPtr<rollbar_jvm::jvmti::_jobject> =
     AtomicPtr::new(std::ptr::null_mut());
 
@@ -28,9 +50,6 @@ pub fn callback(
         return Ok(());
     }
     let exc = jni_env.get_exception_info(exception)?;
-    if should_ignore(exc.class.as_ref()) {
-        return Ok(());
-    }
     debug!("Report: {}", exc.class);
     let mut frames = jni_env.get_stack_trace(exception)?;
     frames.reverse();
@@ -43,33 +62,3 @@ pub fn callback(
     rollbar.send(data);
     Ok(())
 }
-
-fn should_ignore(class: &str) -> bool {
-    lazy_static! {
-        static ref AUT: AcAutomaton<&'static str> = [
-            "com.sun.org",
-            "javax.naming.",
-            "java.io.EOFException",
-            "java.io.FileNotFoundException",
-            "java.io.IOException",
-            "java.lang.ArrayIndexOutOfBoundsException",
-            "java.lang.ClassNotFoundException",
-            "java.lang.IllegalStateException",
-            "java.lang.InterruptedException",
-            "java.lang.NoSuchFieldError",
-            "java.lang.NoSuchFieldException",
-            "java.lang.NoSuchMethodException",
-            "java.net.MalformedURLException",
-            "java.net.SocketException",
-            "java.security.cert.CertificateParsingException",
-            "java.security.PrivilegedActionException",
-            "java.security.SignatureException",
-            "java.util.zip.ZipException",
-            "javax.crypto.BadPaddingException",
-        ]
-        .iter()
-        .cloned()
-        .collect();
-    }
-    AUT.find(class).next().is_some()
-}
@@ -13,19 +13,18 @@ use lazy_static::lazy_static;
 use rollbar_jvm::env::JvmTiEnv;
 use rollbar_jvm::jni::JniEnv;
 use rollbar_jvm::jvmti::*;
-use rollbar_rust::Configuration;
-use std::env;
+use rollbar_rust::configuration::Source;
+use rollbar_rust::ConfigLoader;
 use std::os::raw::{c_char, c_void};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Once;
 
 static INIT_SUCCESS: AtomicBool = AtomicBool::new(false);
 
-static mut CONFIG: Option<Configuration> = None;
+static mut CONFIG: Option<rollbar_rust::Configuration> = None;
 static INIT: Once = Once::new();
 
-const ACCESS_TOKEN_KEY: &str = "ROLLBAR_TOKEN";
-const ENDPOINT_KEY: &str = "ROLLBAR_ENDPOINT";
+const CONFIG_FILE: &str = "rollbar.conf";
 
 lazy_static! {
     static ref ROLLBAR: Rollbar = build_client();
@@ -44,24 +43,27 @@ fn initialize_configuration() -> bool {
     unsafe {
         INIT.call_once(|| {
             debug!("Loading configuration");
-            match Rollbar::configuration_from_file("rollbar.conf") {
-                Ok(conf) => {
-                    CONFIG = Some(conf);
-                }
-                Err(err) => {
-                    debug!("Error loading configuration: {}", err);
-
-                    let mut conf = Configuration::default();
-
-                    if let Ok(access_token) = env::var(ACCESS_TOKEN_KEY) {
-                        conf.access_token = Some(access_token);
+            match ConfigLoader::new().file(CONFIG_FILE).load() {
+                Ok(loaded) => {
+                    for (field, source) in &loaded.sources {
+                        debug!("{} from {}", field, source);
                     }
-
-                    if let Ok(endpoint) = env::var(ENDPOINT_KEY) {
-                        conf.endpoint = endpoint;
+                    let mut configuration = loaded.configuration.clone();
+                    if loaded.source("ignored_exception_classes") == Some(&Source::Default) {
+                        configuration.ignored_exception_classes =
+                            exceptions::DEFAULT_IGNORED_EXCEPTION_CLASSES
+                                .iter()
+                                .map(|&class| class.to_owned())
+                                .collect();
+                    }
+                    match configuration.validate() {
+                        Ok(()) if !configuration.enabled => info!("Reporting disabled"),
+                        Ok(()) => CONFIG = Some(configuration),
+                        Err(err) => error!("Invalid configuration: {}", err),
                     }
-
-                    CONFIG = Some(conf);
+                }
+                Err(err) => {
+                    error!("Error loading configuration: {}", err);
                 }
             }
         });
@@ -82,7 +84,9 @@ pub extern "C" fn Agent_OnLoad(
         return e;
     }
     if !initialize_configuration() {
-        return jvmtiError_JVMTI_ERROR_INTERNAL as i32;
+        // Let the JVM start anyway, without reporting exceptions.
+        warn!("Agent disabled");
+        return 0;
     }
     info!("Agent load complete success");
     INIT_SUCCESS.store(true, Ordering::Relaxed);
@@ -92,9 +96,12 @@ pub extern "C" fn Agent_OnLoad(
 #[no_mangle]
 #[allow(unused_variables)]
 pub extern "C" fn Agent_OnUnload(vm: *mut JavaVM) {
+    if !INIT_SUCCESS.load(Ordering::Relaxed) {
+        return;
+    }
     info!("Agent shutdown begin");
     ROLLBAR.shutdown();
-    info!("Agent shutdown success");
+    info!("Agent shutdown success: {:?}", ROLLBAR.stats());
 }
 
 fn onload(vm: *mut JavaVM) -> Result<(), jint> {
@@ -1,5 +1,5 @@
 declare const promisify: any;
-declare const fromConfig: any, log: any, debug: any, info: any, warning: any, error: any, critical: any, shutdown: any;
+declare const fromConfig: any, log: any, debug: any, info: any, warning: any, error: any, critical: any, shutdown: any, stats: any;
 declare const logAsync: any;
 declare const debugAsync: any;
 declare const infoAsync: any;
@@ -10,6 +10,22 @@ interface Config {
     accessToken: string;
     endpoint?: string;
 }
+interface TransportStats {
+    queued: number;
+    inFlight: number;
+    sent: number;
+    failed: number;
+    retried: number;
+    dropped: {
+        overflow: number;
+        rateLimited: number;
+        sampled: number;
+    };
+    lastError?: string;
+    lastSuccess?: number;
+    latencyP50Ms?: number;
+    latencyP99Ms?: number;
+}
 declare type Level = 'debug' | 'info' | 'warning' | 'error' | 'critical';
 interface ExtraData {
     [key: string]: any;
@@ -24,4 +40,5 @@ declare class Rollbar {
     error(message: string, extra: ExtraData): any;
     critical(message: string, extra: ExtraData): any;
     shutdown(): any;
+    stats(): TransportStats;
 }
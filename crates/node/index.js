@@ -1,5 +1,5 @@
 var promisify = require("util").promisify;
-var _a = require("./index.node"), fromConfig = _a.fromConfig, log = _a.log, debug = _a.debug, info = _a.info, warning = _a.warning, error = _a.error, critical = _a.critical, shutdown = _a.shutdown;
+var _a = require("./index.node"), fromConfig = _a.fromConfig, log = _a.log, debug = _a.debug, info = _a.info, warning = _a.warning, error = _a.error, critical = _a.critical, shutdown = _a.shutdown, stats = _a.stats;
 var logAsync = promisify(log);
 var debugAsync = promisify(debug);
 var infoAsync = promisify(info);
@@ -31,6 +31,9 @@ var Rollbar = /** @class */ (function () {
     Rollbar.prototype.shutdown = function () {
         return shutdown.call(this.instance);
     };
+    Rollbar.prototype.stats = function () {
+        return stats.call(this.instance);
+    };
     return Rollbar;
 }());
 module.exports = Rollbar;
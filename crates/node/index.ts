@@ -9,6 +9,7 @@ const {
     error,
     critical,
     shutdown,
+    stats,
 } = require("./index.node")
 
 const logAsync = promisify(log)
@@ -23,6 +24,19 @@ interface Config {
     endpoint?: string
 }
 
+interface TransportStats {
+    queued: number
+    inFlight: number
+    sent: number
+    failed: number
+    retried: number
+    dropped: { overflow: number, rateLimited: number, sampled: number }
+    lastError?: string
+    lastSuccess?: number
+    latencyP50Ms?: number
+    latencyP99Ms?: number
+}
+
 type Level = 'debug' | 'info' | 'warning' | 'error' | 'critical'
 
 interface ExtraData {
@@ -63,6 +77,10 @@ class Rollbar {
     shutdown() {
         return shutdown.call(this.instance)
     }
+
+    stats(): TransportStats {
+        return stats.call(this.instance)
+    }
 }
 
 module.exports = Rollbar
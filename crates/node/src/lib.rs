@@ -4,13 +4,11 @@ use {
     std::{collections::HashMap, time::Duration},
 };
 
-use rollbar_rust::{
-    types::Level, Body, Configuration, Data, HttpTransport, Item, Message, Transport,
-};
+use rollbar_rust::{types::Level, Configuration, Rollbar as RollbarClient};
 
 #[derive(Debug)]
 pub struct Rollbar {
-    transport: HttpTransport,
+    client: RollbarClient,
 }
 
 impl Finalize for Rollbar {}
@@ -22,17 +20,23 @@ impl Rollbar {
         let config: Configuration =
             neon_serde2::from_value(&mut cx, input).or_else(|e| cx.throw_error(e.to_string()))?;
 
-        let transport = HttpTransport::new(config).or_else(|e| cx.throw_error(e.to_string()))?;
+        let client = RollbarClient::new(config).or_else(|e| cx.throw_error(e.to_string()))?;
 
-        Ok(cx.boxed(Rollbar { transport }))
+        Ok(cx.boxed(Rollbar { client }))
     }
 
     pub fn shutdown(mut cx: FunctionContext) -> JsResult<JsUndefined> {
         let instance = cx.this().downcast_or_throw::<JsBox<Rollbar>, _>(&mut cx)?;
-        instance.transport.shutdown(Duration::from_secs(5));
+        instance.client.shutdown(Duration::from_secs(5));
         Ok(cx.undefined())
     }
 
+    pub fn stats(mut cx: FunctionContext) -> JsResult<JsValue> {
+        let instance = cx.this().downcast_or_throw::<JsBox<Rollbar>, _>(&mut cx)?;
+        let stats = instance.client.stats();
+        neon_serde2::to_value(&mut cx, &stats).or_else(|e| cx.throw_error(e.to_string()))
+    }
+
     pub fn log_with<'a>(
         instance: Handle<JsBox<Self>>,
         level: Level,
@@ -49,25 +53,12 @@ impl Rollbar {
             HashMap::new()
         };
 
-        let message = Message::builder()
-            .body(message.value(&mut cx))
-            .extra(extra)
-            .build();
-        let body = Body::builder().message(message).build();
-        let mut data = Data::default();
-        data.level = Some(level);
-        data.body = body;
-
-        let config = instance.transport.config();
-
-        let access_token = config.access_token.clone().expect("missing access token");
-
-        let item = Item::builder()
-            .access_token(access_token)
-            .data(data)
-            .build();
+        let message = message.value(&mut cx);
 
-        instance.transport.send(item);
+        instance
+            .client
+            .message_with_extra(level, message, extra)
+            .or_else(|e| cx.throw_error(e.to_string()))?;
 
         Ok(cx.undefined())
     }
@@ -122,6 +113,7 @@ pub fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("error", Rollbar::error)?;
     cx.export_function("critical", Rollbar::critical)?;
     cx.export_function("shutdown", Rollbar::shutdown)?;
+    cx.export_function("stats", Rollbar::stats)?;
 
     Ok(())
 }
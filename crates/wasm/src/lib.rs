@@ -1,13 +1,11 @@
 use {std::collections::HashMap, wasm_bindgen::prelude::*};
 
-use rollbar_rust::{
-    types::Level, Body, Configuration, Data, HttpTransport, Item, Message, Transport,
-};
+use rollbar_rust::{types::Level, Configuration, Rollbar as RollbarClient};
 
 #[derive(Debug)]
 #[wasm_bindgen]
 pub struct Rollbar {
-    transport: HttpTransport,
+    client: RollbarClient,
 }
 
 #[wasm_bindgen]
@@ -18,39 +16,21 @@ impl Rollbar {
             .into_serde()
             .map_err(|err| JsValue::from(format!("invalid configuration object: {}", err)))?;
 
-        let transport = HttpTransport::new(config)
-            .map_err(|err| JsValue::from(format!("could not create transport: {}", err)))?;
+        let client = RollbarClient::new(config).map_err(|err| JsValue::from(err.to_string()))?;
 
-        Ok(Rollbar { transport })
+        Ok(Rollbar { client })
     }
 
     pub fn log(&self, level: &str, message: &str, extra: JsValue) -> Result<(), JsValue> {
         let extra: HashMap<_, _> = extra.into_serde().unwrap_or_default();
-        let message = Message::builder().body(message).extra(extra).build();
 
-        let body = Body::builder().message(message).build();
-
-        let data = Data {
-            level: Some(Level::from(level)),
-            body,
-            ..Data::default()
-        };
-
-        let config = self.transport.config();
-
-        let access_token = config
-            .access_token
-            .as_ref()
-            .ok_or(JsValue::from_str("missing access token"))?;
-
-        let item = Item::builder()
-            .access_token(access_token)
-            .data(data)
-            .build();
-
-        self.transport.send(item);
+        self.client
+            .message_with_extra(Level::from(level), message, extra)
+            .map_err(|err| JsValue::from(err.to_string()))
+    }
 
-        Ok(())
+    pub fn stats(&self) -> Result<JsValue, JsValue> {
+        JsValue::from_serde(&self.client.stats()).map_err(|err| JsValue::from(err.to_string()))
     }
 
     pub fn debug(&self, message: &str, extra: JsValue) -> Result<(), JsValue> {